@@ -5,25 +5,36 @@ use letterbox::game::{
     astar::{manhattan_heuristic, AStar},
     matrix::Matrix,
     node::{Entry, Node},
+    packed_matrix::PackedMatrix,
 };
 
-fn astar(s: usize) -> Option<Vec<(usize, usize)>> {
+// both grids are built before the timing starts, so that only the search is measured,
+// the game itself paths over Matrix<Node>
+fn astar<M: AStar>(m: &M, goal: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+    m.astar((0, 0), goal, &manhattan_heuristic, &HashMap::new())
+}
+
+fn matrix(s: usize) -> Matrix<Node> {
     let mut m = Matrix::new(s, s, Node::open());
 
     m[(1, 9)][Entry::LEFT] = false;
     m[(1, 9)][Entry::TOP] = false;
 
-    m.astar(
-        (0, 0),
-        (s - 1, s - 1),
-        &manhattan_heuristic,
-        &HashMap::new(),
-    )
+    m
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
-    c.bench_function("astar 100", |b| b.iter(|| astar(black_box(100))));
-    c.bench_function("astar 1000", |b| b.iter(|| astar(black_box(1000))));
+    for s in [100, 1000] {
+        let m = matrix(s);
+        let packed = PackedMatrix::from(&m);
+
+        c.bench_function(&format!("astar {s}"), |b| {
+            b.iter(|| astar(black_box(&m), (s - 1, s - 1)))
+        });
+        c.bench_function(&format!("astar packed {s}"), |b| {
+            b.iter(|| astar(black_box(&packed), (s - 1, s - 1)))
+        });
+    }
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,7 +1,23 @@
+pub mod archetype;
 pub mod astar;
+pub mod autotile;
+pub mod behaviour;
+pub mod brush;
+pub mod chunk;
 pub mod coordinates;
+pub mod door;
+pub mod dungeon;
+pub mod edit_history;
 pub mod encoded_matrix;
+pub mod hazard;
+pub mod level;
+pub mod map_gen;
 pub mod matrix;
 pub mod movement;
 pub mod node;
+pub mod packed_matrix;
 pub mod path_node;
+pub mod rng;
+pub mod spawn;
+pub mod steering;
+pub mod wave;
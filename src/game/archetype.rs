@@ -0,0 +1,203 @@
+use std::path::PathBuf;
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use super::{behaviour::BehaviourConfig, coordinates::Coordinates};
+
+// walkers take hazards and avoid them on their paths, flyers pass over them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum MovementMode {
+    Walk,
+    Fly,
+}
+
+// shots are fired while attacking, the attack range of the behaviour is the firing range,
+// damage is the durability of the projectile
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RangedAttack {
+    pub cooldown: f32,
+    pub damage: u16,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum BossPattern {
+    // projectiles in every direction at once, with a blast radius they blow up the walls they hit
+    Ring {
+        count: usize,
+        damage: u16,
+        #[serde(default)]
+        blast: usize,
+    },
+    // regular enemies around the boss
+    Summon {
+        count: usize,
+    },
+    // runs at the player this many times faster for a while
+    Charge {
+        speed: f32,
+        time: f32,
+    },
+}
+
+// a phase starts once the share of health left drops below `below`,
+// its patterns take turns every cooldown while the boss fights
+#[derive(Debug, Clone, Deserialize)]
+pub struct BossPhase {
+    pub below: f32,
+    pub cooldown: f32,
+    pub patterns: Vec<BossPattern>,
+}
+
+// bosses appear once on each of their floors, `after` seconds on the floor,
+// the footprint is the side of the square of cells they cover
+#[derive(Debug, Clone, Deserialize)]
+pub struct BossConfig {
+    pub footprint: usize,
+    pub floors: Vec<usize>,
+    #[serde(default)]
+    pub after: f32,
+    pub phases: Vec<BossPhase>,
+}
+
+// one horizontal strip of square frames per direction, the paths are relative to the assets
+#[derive(Debug, Deserialize)]
+struct SpriteSheetDef {
+    size: f32,
+    frames: usize,
+    frame_time: f32,
+    up: String,
+    down: String,
+    left: String,
+    right: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EnemyArchetypeDef {
+    name: String,
+    health: u16,
+    speed: f32,
+    scale: (f32, f32),
+    damage: u16,
+    movement: MovementMode,
+    weight: u32,
+    #[serde(default)]
+    behaviour: BehaviourConfig,
+    #[serde(default)]
+    ranged: Option<RangedAttack>,
+    #[serde(default)]
+    boss: Option<BossConfig>,
+    sprites: SpriteSheetDef,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectionalSprites {
+    pub size: f32,
+    pub frame_time: f32,
+    pub up: Handle<TextureAtlas>,
+    pub down: Handle<TextureAtlas>,
+    pub left: Handle<TextureAtlas>,
+    pub right: Handle<TextureAtlas>,
+}
+
+impl DirectionalSprites {
+    pub fn find(&self, from: &Coordinates, to: &Coordinates) -> Handle<TextureAtlas> {
+        if from.1 > to.1 {
+            self.left.clone()
+        } else if from.1 < to.1 {
+            self.right.clone()
+        } else if from.0 > to.0 {
+            self.up.clone()
+        } else {
+            self.down.clone()
+        }
+    }
+}
+
+// an `*.enemy.ron` file in assets/enemies, health is scaled by the floor,
+// speed divides the step time and the scale is the range of sprite sizes in cells,
+// the behaviour falls back to the defaults for every field left out
+#[derive(TypeUuid, Debug, Clone)]
+#[uuid = "d24c5885-b5f5-41be-8288-b60c16dd7c79"]
+pub struct EnemyArchetype {
+    pub name: String,
+    pub health: u16,
+    pub speed: f32,
+    pub scale: (f32, f32),
+    pub damage: u16,
+    pub movement: MovementMode,
+    pub weight: u32,
+    pub behaviour: BehaviourConfig,
+    pub ranged: Option<RangedAttack>,
+    pub boss: Option<BossConfig>,
+    pub sprites: DirectionalSprites,
+}
+
+impl EnemyArchetype {
+    // side of the square of cells the enemy covers
+    pub fn footprint(&self) -> usize {
+        self.boss.as_ref().map_or(1, |it| it.footprint.max(1))
+    }
+}
+
+#[derive(Default)]
+pub struct EnemyArchetypeLoader;
+
+impl AssetLoader for EnemyArchetypeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let def: EnemyArchetypeDef = ron::de::from_bytes(bytes)?;
+            let sheet = &def.sprites;
+            let mut atlas = |label: &str, path: &str| {
+                let path = AssetPath::new(PathBuf::from(path), None);
+                let atlas = TextureAtlas::from_grid(
+                    load_context.get_handle(path.clone()),
+                    Vec2::new(sheet.size, sheet.size),
+                    sheet.frames,
+                    1,
+                    None,
+                    None,
+                );
+
+                load_context.set_labeled_asset(label, LoadedAsset::new(atlas).with_dependency(path))
+            };
+            let sprites = DirectionalSprites {
+                size: sheet.size,
+                frame_time: sheet.frame_time,
+                up: atlas("up", &sheet.up),
+                down: atlas("down", &sheet.down),
+                left: atlas("left", &sheet.left),
+                right: atlas("right", &sheet.right),
+            };
+
+            load_context.set_default_asset(LoadedAsset::new(EnemyArchetype {
+                name: def.name,
+                health: def.health,
+                speed: def.speed,
+                scale: def.scale,
+                damage: def.damage,
+                movement: def.movement,
+                weight: def.weight,
+                behaviour: def.behaviour,
+                ranged: def.ranged,
+                boss: def.boss,
+                sprites,
+            }));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemy.ron"]
+    }
+}
@@ -1,9 +1,8 @@
 use std::collections::{BinaryHeap, HashMap};
 
 use super::coordinates::Coordinates;
-use super::matrix::Matrix;
-use super::movement::Movement;
-use super::{node::Node, path_node::PathNode};
+use super::movement::{Movement, NodeGrid};
+use super::path_node::PathNode;
 
 const WEIGHT: i32 = 1;
 
@@ -24,7 +23,10 @@ pub fn manhattan_heuristic(a: &Coordinates, b: &Coordinates) -> i32 {
     dx + dy
 }
 
-impl AStar for Matrix<Node> {
+impl<T> AStar for T
+where
+    T: NodeGrid,
+{
     fn astar(
         &self,
         start: Coordinates,
@@ -50,11 +52,12 @@ impl AStar for Matrix<Node> {
             for n in self.nearest_neighbours(&current.index) {
                 if let Some(index) = n {
                     if !closed.contains_key(&index) {
-                        let visited = lookup.get(&index);
-                        let g_score_n = *visited.unwrap_or(&WEIGHT);
-                        let g = g_score_self + g_score_n;
+                        // the step cost weighs the edge, a cheaper way to a node already seen
+                        // replaces the one it was found with
+                        let visited = lookup.get(&index).copied();
+                        let g = g_score_self + self.step_cost(&index);
 
-                        if visited.is_none() || g < g_score_n {
+                        if visited.is_none_or(|it| g < it) {
                             let h = heuristic(&index, &goal);
                             let path_node = PathNode {
                                 index,
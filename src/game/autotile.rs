@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use bevy::prelude::{warn, Resource};
+use serde::Deserialize;
+
+use super::{coordinates::Coordinates, matrix::Matrix, node::Node};
+
+pub const NORTH: u8 = 1;
+pub const NORTH_EAST: u8 = 2;
+pub const EAST: u8 = 4;
+pub const SOUTH_EAST: u8 = 8;
+pub const SOUTH: u8 = 16;
+pub const SOUTH_WEST: u8 = 32;
+pub const WEST: u8 = 64;
+pub const NORTH_WEST: u8 = 128;
+
+// (row offset, col offset, bit), clockwise starting north
+const NEIGHBOURS: [(i64, i64, u8); 8] = [
+    (-1, 0, NORTH),
+    (-1, 1, NORTH_EAST),
+    (0, 1, EAST),
+    (1, 1, SOUTH_EAST),
+    (1, 0, SOUTH),
+    (1, -1, SOUTH_WEST),
+    (0, -1, WEST),
+    (-1, -1, NORTH_WEST),
+];
+
+// a bit is set when that neighbour is a wall as well, the border of the map counts as wall
+pub fn wall_mask(matrix: &Matrix<Node>, coordinates: &Coordinates) -> u8 {
+    NEIGHBOURS
+        .iter()
+        .filter(|(row, col, _)| {
+            let row = coordinates.0 as i64 + row;
+            let col = coordinates.1 as i64 + col;
+
+            row < 0
+                || col < 0
+                || !matrix.contains(&(row as usize, col as usize))
+                || matrix[(row as usize, col as usize)].is_wall()
+        })
+        .fold(0, |mask, (_, _, bit)| mask | bit)
+}
+
+// a corner only matters when both of its sides are walls, which leaves 47 distinct masks
+pub fn reduce_mask(mask: u8) -> u8 {
+    [
+        (NORTH_EAST, NORTH | EAST),
+        (SOUTH_EAST, SOUTH | EAST),
+        (SOUTH_WEST, SOUTH | WEST),
+        (NORTH_WEST, NORTH | WEST),
+    ]
+    .iter()
+    .fold(mask, |mask, (corner, sides)| match mask & sides == *sides {
+        true => mask,
+        false => mask & !corner,
+    })
+}
+
+// the 47 reduced masks in ascending order
+pub fn blob_masks() -> Vec<u8> {
+    let mut masks: Vec<u8> = (0..=255).map(reduce_mask).collect();
+
+    masks.sort_unstable();
+    masks.dedup();
+
+    masks
+}
+
+// the cell itself and every cell whose wall tile depends on it
+pub fn affected_cells(coordinates: &Coordinates) -> impl Iterator<Item = Coordinates> {
+    let coordinates = *coordinates;
+
+    std::iter::once((0, 0, 0))
+        .chain(NEIGHBOURS)
+        .filter_map(move |(row, col, _)| {
+            let row = coordinates.0 as i64 + row;
+            let col = coordinates.1 as i64 + col;
+
+            (row >= 0 && col >= 0).then_some((row as usize, col as usize))
+        })
+}
+
+#[derive(Resource, Deserialize, Debug, Clone, Default)]
+pub enum WallTileset {
+    // the 16 wall_XXXX.png images, picked by the 4 orthogonal neighbours only
+    #[default]
+    Legacy,
+    // a single atlas image, every reduced 8-neighbour mask maps onto a tile index,
+    // masks missing from the mapping use their position in `blob_masks`
+    Blob {
+        image: String,
+        tile_size: (f32, f32),
+        columns: usize,
+        #[serde(default)]
+        mapping: HashMap<u8, usize>,
+    },
+}
+
+impl WallTileset {
+    pub fn from_file(file_name: &str) -> Self {
+        std::fs::read_to_string(file_name)
+            .map_err(|error| error.to_string())
+            .and_then(|raw| ron::from_str::<Self>(&raw).map_err(|error| error.to_string()))
+            .map(Self::with_default_mapping)
+            .unwrap_or_else(|error| {
+                warn!(
+                    "could not load {}, using the legacy wall tiles: {}",
+                    file_name, error
+                );
+
+                Self::default()
+            })
+    }
+
+    fn with_default_mapping(mut self) -> Self {
+        if let WallTileset::Blob { mapping, .. } = &mut self {
+            for (index, mask) in blob_masks().into_iter().enumerate() {
+                mapping.entry(mask).or_insert(index);
+            }
+        }
+
+        self
+    }
+
+    // (x, y) pixel offset of the wall tile within the atlas image
+    pub fn blob_tile(&self, mask: u8) -> Option<(f32, f32)> {
+        match self {
+            WallTileset::Legacy => None,
+            WallTileset::Blob {
+                tile_size,
+                columns,
+                mapping,
+                ..
+            } => {
+                let index = mapping.get(&reduce_mask(mask)).copied().unwrap_or(0);
+
+                Some((
+                    (index % columns) as f32 * tile_size.0,
+                    (index / columns) as f32 * tile_size.1,
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_without_both_sides_are_dropped() {
+        assert_eq!(reduce_mask(NORTH_EAST), 0);
+        assert_eq!(reduce_mask(NORTH | NORTH_EAST), NORTH);
+        assert_eq!(
+            reduce_mask(NORTH | EAST | NORTH_EAST),
+            NORTH | EAST | NORTH_EAST
+        );
+        assert_eq!(reduce_mask(255), 255);
+    }
+
+    #[test]
+    fn there_are_47_blob_masks() {
+        let masks = blob_masks();
+
+        assert_eq!(masks.len(), 47);
+        assert!(masks.iter().all(|it| reduce_mask(*it) == *it));
+    }
+
+    #[test]
+    fn the_border_counts_as_wall() {
+        let matrix = Matrix::new(3, 3, Node::open());
+
+        assert_eq!(wall_mask(&matrix, &(1, 1)), 0);
+        assert_eq!(
+            wall_mask(&matrix, &(0, 0)),
+            NORTH | NORTH_EAST | SOUTH_WEST | WEST | NORTH_WEST
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use rand::prelude::*;
+use serde::Deserialize;
+
+use super::{brush::line, coordinates::Coordinates, matrix::Matrix, node::Node};
+
+// random cells tried when looking for somewhere to roam or flee to
+const TARGET_TRIES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BehaviourState {
+    Idle,
+    Wander,
+    Patrol,
+    Chase,
+    Attack,
+    Flee,
+}
+
+// distances are manhattan distances in cells, health is the share of the full health,
+// enemies below flee_below run from the player once they see them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BehaviourConfig {
+    pub sight_range: i32,
+    pub attack_range: i32,
+    pub give_up_range: i32,
+    pub line_of_sight: bool,
+    pub flee_below: f32,
+    // seconds of rest between roaming, (min, max)
+    pub idle_time: (f32, f32),
+    pub roam_radius: usize,
+    // patrolling goes back and forth between the spawn and one other cell instead of wandering
+    pub patrol: bool,
+}
+
+impl Default for BehaviourConfig {
+    fn default() -> Self {
+        Self {
+            sight_range: 8,
+            attack_range: 1,
+            give_up_range: 14,
+            line_of_sight: true,
+            flee_below: 0.,
+            idle_time: (1., 3.),
+            roam_radius: 6,
+            patrol: false,
+        }
+    }
+}
+
+// what an enemy knows when it decides on its next state
+#[derive(Debug, Clone, Copy)]
+pub struct Senses {
+    pub distance: i32,
+    pub in_sight: bool,
+    pub health: f32,
+    // at the end of the current path
+    pub arrived: bool,
+    // the idle time is over
+    pub rested: bool,
+}
+
+impl BehaviourState {
+    pub fn next(self, config: &BehaviourConfig, senses: &Senses) -> Self {
+        let roam = match config.patrol {
+            true => BehaviourState::Patrol,
+            false => BehaviourState::Wander,
+        };
+
+        match self {
+            _ if senses.health < config.flee_below && senses.in_sight => BehaviourState::Flee,
+            BehaviourState::Flee if senses.distance > config.give_up_range => BehaviourState::Idle,
+            BehaviourState::Flee => BehaviourState::Flee,
+            BehaviourState::Idle | BehaviourState::Wander | BehaviourState::Patrol
+                if senses.in_sight =>
+            {
+                BehaviourState::Chase
+            }
+            BehaviourState::Idle if senses.rested => roam,
+            BehaviourState::Idle => BehaviourState::Idle,
+            BehaviourState::Wander | BehaviourState::Patrol if senses.arrived => {
+                BehaviourState::Idle
+            }
+            BehaviourState::Wander | BehaviourState::Patrol => self,
+            BehaviourState::Chase | BehaviourState::Attack
+                if senses.distance > config.give_up_range =>
+            {
+                BehaviourState::Idle
+            }
+            BehaviourState::Chase | BehaviourState::Attack
+                if senses.distance <= config.attack_range && senses.in_sight =>
+            {
+                BehaviourState::Attack
+            }
+            BehaviourState::Chase | BehaviourState::Attack => BehaviourState::Chase,
+        }
+    }
+}
+
+// walls block the view, doors and gates do not
+pub fn line_of_sight(matrix: &Matrix<Node>, from: &Coordinates, to: &Coordinates) -> bool {
+    line(from, to)
+        .iter()
+        .all(|it| matrix.contains(it) && !matrix[*it].is_wall())
+}
+
+fn random_cell(
+    matrix: &Matrix<Node>,
+    center: &Coordinates,
+    radius: usize,
+    rng: &mut StdRng,
+) -> Option<Coordinates> {
+    let row = rng.gen_range(center.0.saturating_sub(radius)..=center.0 + radius);
+    let col = rng.gen_range(center.1.saturating_sub(radius)..=center.1 + radius);
+
+    (matrix.contains(&(row, col)) && !matrix[(row, col)].is_wall()).then_some((row, col))
+}
+
+// somewhere open around the center, the center itself when nothing is found
+pub fn roam_target(
+    matrix: &Matrix<Node>,
+    center: &Coordinates,
+    radius: usize,
+    rng: &mut StdRng,
+) -> Coordinates {
+    (0..TARGET_TRIES)
+        .find_map(|_| random_cell(matrix, center, radius, rng))
+        .unwrap_or(*center)
+}
+
+// the open cell around the enemy that is farthest from the threat
+pub fn flee_target(
+    matrix: &Matrix<Node>,
+    from: &Coordinates,
+    threat: &Coordinates,
+    radius: usize,
+    rng: &mut StdRng,
+) -> Coordinates {
+    let distance = |it: &Coordinates| it.0.abs_diff(threat.0) + it.1.abs_diff(threat.1);
+
+    (0..TARGET_TRIES)
+        .filter_map(|_| random_cell(matrix, from, radius, rng))
+        .max_by_key(distance)
+        .filter(|it| distance(it) > distance(from))
+        .unwrap_or(*from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn senses(distance: i32, in_sight: bool) -> Senses {
+        Senses {
+            distance,
+            in_sight,
+            health: 1.,
+            arrived: false,
+            rested: false,
+        }
+    }
+
+    #[test]
+    fn roams_until_it_sees_the_player() {
+        let config = BehaviourConfig::default();
+        let rested = Senses {
+            rested: true,
+            ..senses(20, false)
+        };
+        let arrived = Senses {
+            arrived: true,
+            ..senses(20, false)
+        };
+
+        assert_eq!(
+            BehaviourState::Idle.next(&config, &senses(20, false)),
+            BehaviourState::Idle
+        );
+        assert_eq!(
+            BehaviourState::Idle.next(&config, &rested),
+            BehaviourState::Wander
+        );
+        assert_eq!(
+            BehaviourState::Idle.next(
+                &BehaviourConfig {
+                    patrol: true,
+                    ..config.clone()
+                },
+                &rested
+            ),
+            BehaviourState::Patrol
+        );
+        assert_eq!(
+            BehaviourState::Wander.next(&config, &arrived),
+            BehaviourState::Idle
+        );
+        assert_eq!(
+            BehaviourState::Wander.next(&config, &senses(5, true)),
+            BehaviourState::Chase
+        );
+    }
+
+    #[test]
+    fn attacks_in_range_and_gives_up_far_away() {
+        let config = BehaviourConfig::default();
+
+        assert_eq!(
+            BehaviourState::Chase.next(&config, &senses(1, true)),
+            BehaviourState::Attack
+        );
+        // out of sight it keeps chasing instead of attacking
+        assert_eq!(
+            BehaviourState::Chase.next(&config, &senses(1, false)),
+            BehaviourState::Chase
+        );
+        assert_eq!(
+            BehaviourState::Attack.next(&config, &senses(3, true)),
+            BehaviourState::Chase
+        );
+        assert_eq!(
+            BehaviourState::Chase.next(&config, &senses(15, false)),
+            BehaviourState::Idle
+        );
+    }
+
+    #[test]
+    fn flees_when_hurt_until_far_enough() {
+        let config = BehaviourConfig {
+            flee_below: 0.5,
+            ..Default::default()
+        };
+        let hurt = Senses {
+            health: 0.2,
+            ..senses(3, true)
+        };
+
+        assert_eq!(
+            BehaviourState::Chase.next(&config, &hurt),
+            BehaviourState::Flee
+        );
+        assert_eq!(
+            BehaviourState::Flee.next(&config, &senses(10, false)),
+            BehaviourState::Flee
+        );
+        assert_eq!(
+            BehaviourState::Flee.next(&config, &senses(15, false)),
+            BehaviourState::Idle
+        );
+    }
+}
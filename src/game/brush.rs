@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+
+use super::{coordinates::Coordinates, matrix::Matrix};
+
+// cells on the straight line between two cells, see Bresenham
+pub fn line(from: &Coordinates, to: &Coordinates) -> Vec<Coordinates> {
+    let (mut row, mut col) = (from.0 as i64, from.1 as i64);
+    let (to_row, to_col) = (to.0 as i64, to.1 as i64);
+    let d_row = -(to_row - row).abs();
+    let d_col = (to_col - col).abs();
+    let step_row = if row < to_row { 1 } else { -1 };
+    let step_col = if col < to_col { 1 } else { -1 };
+    let mut error = d_col + d_row;
+    let mut cells = vec![*from];
+
+    while (row, col) != (to_row, to_col) {
+        let double_error = 2 * error;
+
+        if double_error >= d_row {
+            error += d_row;
+            col += step_col;
+        }
+
+        if double_error <= d_col {
+            error += d_col;
+            row += step_row;
+        }
+
+        cells.push((row as usize, col as usize));
+    }
+
+    cells
+}
+
+// every cell within the rectangle spanned by two corner cells
+pub fn rectangle(from: &Coordinates, to: &Coordinates) -> Vec<Coordinates> {
+    let rows = from.0.min(to.0)..=from.0.max(to.0);
+    let cols = from.1.min(to.1)..=from.1.max(to.1);
+
+    rows.flat_map(|row| cols.clone().map(move |col| (row, col)))
+        .collect()
+}
+
+// the 4-connected area of cells that hold the same value as the start cell
+pub fn flood_fill<T: Clone + PartialEq>(
+    matrix: &Matrix<T>,
+    start: &Coordinates,
+) -> Vec<Coordinates> {
+    if !matrix.contains(start) {
+        return Vec::new();
+    }
+
+    let value = &matrix[*start];
+    let mut visited = vec![false; matrix.rows * matrix.cols];
+    let mut queue = VecDeque::from([*start]);
+    let mut cells = Vec::new();
+
+    visited[start.0 * matrix.cols + start.1] = true;
+
+    while let Some(coordinates) = queue.pop_front() {
+        cells.push(coordinates);
+
+        let neighbours = [
+            coordinates.1.checked_sub(1).map(|col| (coordinates.0, col)),
+            coordinates.0.checked_sub(1).map(|row| (row, coordinates.1)),
+            Some((coordinates.0, coordinates.1 + 1)),
+            Some((coordinates.0 + 1, coordinates.1)),
+        ];
+
+        for neighbour in neighbours.into_iter().flatten() {
+            let index = neighbour.0 * matrix.cols + neighbour.1;
+
+            if matrix.contains(&neighbour) && !visited[index] && &matrix[neighbour] == value {
+                visited[index] = true;
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_steps_from_cell_to_neighbouring_cell() {
+        for to in [(0, 9), (9, 0), (4, 7), (9, 9), (2, 2)] {
+            let cells = line(&(2, 2), &to);
+
+            assert_eq!(cells.first(), Some(&(2, 2)));
+            assert_eq!(cells.last(), Some(&to));
+            assert!(cells
+                .windows(2)
+                .all(|it| it[0].0.abs_diff(it[1].0) <= 1 && it[0].1.abs_diff(it[1].1) <= 1));
+        }
+
+        assert_eq!(line(&(5, 1), &(5, 4)), vec![(5, 1), (5, 2), (5, 3), (5, 4)]);
+    }
+
+    #[test]
+    fn rectangle_covers_the_corners_in_any_order() {
+        let cells = rectangle(&(3, 1), &(1, 4));
+
+        assert_eq!(cells.len(), 12);
+        assert_eq!(cells, rectangle(&(1, 4), &(3, 1)));
+        assert!(cells.contains(&(1, 1)) && cells.contains(&(3, 4)));
+    }
+
+    #[test]
+    fn flood_fill_stays_within_equal_cells() {
+        let mut matrix = Matrix::new(5, 5, 0);
+
+        for row in 0..5 {
+            matrix[(row, 2)] = 1;
+        }
+
+        let cells = flood_fill(&matrix, &(4, 0));
+
+        assert_eq!(cells.len(), 10);
+        assert!(cells.iter().all(|it| it.1 < 2));
+        assert_eq!(flood_fill(&matrix, &(0, 2)).len(), 5);
+        assert!(flood_fill(&matrix, &(5, 0)).is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use super::{
+    coordinates::Coordinates,
+    level::{Level, Placement},
+    matrix::Matrix,
+};
+
+pub const CHUNK_SIZE: usize = 16;
+
+// (row, col) of a chunk, not of a cell
+pub type ChunkCoordinates = (usize, usize);
+
+pub fn chunk_of(coordinates: &Coordinates) -> ChunkCoordinates {
+    (coordinates.0 / CHUNK_SIZE, coordinates.1 / CHUNK_SIZE)
+}
+
+pub fn chunk_origin(chunk: &ChunkCoordinates) -> Coordinates {
+    (chunk.0 * CHUNK_SIZE, chunk.1 * CHUNK_SIZE)
+}
+
+// every chunk of a matrix with the given chunk count, row by row
+pub fn all_chunks(chunk_count: (usize, usize)) -> impl Iterator<Item = ChunkCoordinates> {
+    (0..chunk_count.0).flat_map(move |row| (0..chunk_count.1).map(move |col| (row, col)))
+}
+
+impl<T> Matrix<T>
+where
+    T: Clone + PartialEq,
+{
+    pub fn chunk_count(&self) -> (usize, usize) {
+        (
+            self.rows.div_ceil(CHUNK_SIZE),
+            self.cols.div_ceil(CHUNK_SIZE),
+        )
+    }
+
+    pub fn chunk_cells(&self, chunk: &ChunkCoordinates) -> impl Iterator<Item = Coordinates> {
+        let origin = chunk_origin(chunk);
+        let rows = origin.0..(origin.0 + CHUNK_SIZE).min(self.rows);
+        let cols = origin.1..(origin.1 + CHUNK_SIZE).min(self.cols);
+
+        rows.flat_map(move |row| cols.clone().map(move |col| (row, col)))
+    }
+
+    pub fn chunk(&self, chunk: &ChunkCoordinates) -> Matrix<T> {
+        let origin = chunk_origin(chunk);
+        let rows = CHUNK_SIZE.min(self.rows - origin.0);
+        let cols = CHUNK_SIZE.min(self.cols - origin.1);
+
+        Matrix::from_cells(
+            rows,
+            cols,
+            self[origin].clone(),
+            self.chunk_cells(chunk).map(|it| self[it].clone()),
+        )
+    }
+
+    // loads the chunk if it was not, only cells that differ from what was there are logged
+    pub fn paste_chunk(&mut self, chunk: &ChunkCoordinates, source: &Matrix<T>) {
+        let origin = chunk_origin(chunk);
+
+        self.load_chunk(chunk);
+
+        for row in 0..source.rows {
+            for col in 0..source.cols {
+                let coordinates = (origin.0 + row, origin.1 + col);
+
+                if self.contains(&coordinates) {
+                    self.set(coordinates, source[(row, col)].clone());
+                }
+            }
+        }
+    }
+}
+
+impl Level {
+    pub fn chunk(&self, chunk: &ChunkCoordinates) -> Level {
+        Level {
+            floor: self.floor.chunk(chunk),
+            decoration: self.decoration.chunk(chunk),
+            wall_damage: self.wall_damage.chunk(chunk),
+            hazard: self.hazard.chunk(chunk),
+            placements: self.chunk_placements(chunk),
+        }
+    }
+
+    // the placements in the chunk, relative to its origin like the cells
+    pub fn chunk_placements(&self, chunk: &ChunkCoordinates) -> Vec<Placement> {
+        let origin = chunk_origin(chunk);
+
+        self.placements
+            .iter()
+            .filter(|it| chunk_of(&it.coordinates) == *chunk)
+            .map(|it| Placement {
+                kind: it.kind,
+                coordinates: (it.coordinates.0 - origin.0, it.coordinates.1 - origin.1),
+            })
+            .collect()
+    }
+
+    // placements are kept for the whole floor and are left alone
+    pub fn paste_chunk(&mut self, chunk: &ChunkCoordinates, source: &Level) {
+        self.floor.paste_chunk(chunk, &source.floor);
+        self.decoration.paste_chunk(chunk, &source.decoration);
+        self.wall_damage.paste_chunk(chunk, &source.wall_damage);
+        self.hazard.paste_chunk(chunk, &source.hazard);
+    }
+
+    pub fn unload_chunk(&mut self, chunk: &ChunkCoordinates) {
+        self.floor.unload_chunk(chunk);
+        self.decoration.unload_chunk(chunk);
+        self.wall_damage.unload_chunk(chunk);
+        self.hazard.unload_chunk(chunk);
+    }
+}
@@ -0,0 +1,611 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Index;
+
+use bevy::prelude::{Component, Resource};
+use rand::prelude::*;
+
+use super::{
+    chunk::chunk_of,
+    coordinates::Coordinates,
+    hazard::Hazard,
+    level::{Level, Placement, PlacementKind},
+    matrix::Matrix,
+    movement::{Movement, NodeGrid},
+    node::{Entry, Node},
+    packed_matrix::NODES,
+};
+
+// at most this many doors and gates are tried on a generated floor
+const DOORS_PER_FLOOR: usize = 6;
+const GATES_PER_FLOOR: usize = 2;
+// doors and gates closer than this many cells to each other are skipped
+const BARRIER_SPACING: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyColor {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+}
+
+impl From<KeyColor> for u8 {
+    fn from(value: KeyColor) -> Self {
+        match value {
+            KeyColor::Red => 0,
+            KeyColor::Green => 1,
+            KeyColor::Blue => 2,
+            KeyColor::Yellow => 3,
+        }
+    }
+}
+
+pub const KEY_COLORS: [KeyColor; 4] = [
+    KeyColor::Red,
+    KeyColor::Green,
+    KeyColor::Blue,
+    KeyColor::Yellow,
+];
+
+// doors are opened by agents, optionally with a key, gates only by their switches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Barrier {
+    Door(Option<KeyColor>),
+    Gate(u8),
+}
+
+// what an agent may pass, on the live matrix switches stay empty and gates are only passable
+// while open, planning fills them in for every switch the agent can get to
+#[derive(Component, Debug, Clone, Default)]
+pub struct AgentRules {
+    pub opens_doors: bool,
+    pub keys: HashSet<KeyColor>,
+    pub switches: HashSet<u8>,
+}
+
+impl AgentRules {
+    pub fn player() -> Self {
+        Self {
+            opens_doors: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn enemy() -> Self {
+        Self::default()
+    }
+
+    pub fn can_pass(&self, barrier: &Barrier) -> bool {
+        match barrier {
+            Barrier::Door(lock) => {
+                self.opens_doors && lock.is_none_or(|it| self.keys.contains(&it))
+            }
+            Barrier::Gate(channel) => self.switches.contains(channel),
+        }
+    }
+}
+
+// a barrier closes an edge of its cell together with the facing edge of the neighbour,
+// so that it blocks both ways, both halves are indexed here
+#[derive(Resource, Debug, Default)]
+pub struct Doors(HashMap<Coordinates, Vec<(Entry, Barrier)>>);
+
+impl Doors {
+    pub fn from_level(level: &Level, matrix: &Matrix<Node>) -> Self {
+        let mut doors = Doors::default();
+
+        for placement in &level.placements {
+            doors.add(matrix, placement);
+        }
+
+        doors
+    }
+
+    pub fn add(&mut self, matrix: &Matrix<Node>, placement: &Placement) {
+        for (coordinates, edge, barrier) in halves(matrix, placement) {
+            self.0.entry(coordinates).or_default().push((edge, barrier));
+        }
+    }
+
+    pub fn remove(&mut self, matrix: &Matrix<Node>, placement: &Placement) {
+        for (coordinates, edge, barrier) in halves(matrix, placement) {
+            if let Some(barriers) = self.0.get_mut(&coordinates) {
+                if let Some(index) = barriers.iter().position(|it| *it == (edge, barrier)) {
+                    barriers.remove(index);
+                }
+
+                if barriers.is_empty() {
+                    self.0.remove(&coordinates);
+                }
+            }
+        }
+    }
+
+    pub fn at(&self, coordinates: &Coordinates) -> &[(Entry, Barrier)] {
+        self.0.get(coordinates).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // the barrier on the edge that a step from one cell to the next crosses
+    pub fn crossed(&self, from: &Coordinates, to: &Coordinates) -> Option<(Entry, Barrier)> {
+        let edge = edge_towards(from, to)?;
+
+        self.at(from).iter().find(|it| it.0 == edge).copied()
+    }
+}
+
+// every edge a door or gate closes, each with the cell it belongs to
+fn halves(matrix: &Matrix<Node>, placement: &Placement) -> Vec<(Coordinates, Entry, Barrier)> {
+    let (edges, barrier) = match placement.kind {
+        PlacementKind::Door { edge, lock } => (vec![edge], Barrier::Door(lock)),
+        PlacementKind::Gate { edge, channel } => {
+            (vec![edge, edge.opposite()], Barrier::Gate(channel))
+        }
+        _ => return Vec::new(),
+    };
+    let coordinates = placement.coordinates;
+
+    edges
+        .into_iter()
+        .flat_map(|edge| {
+            std::iter::once((coordinates, edge, barrier)).chain(
+                neighbour(matrix, &coordinates, &edge).map(|it| (it, edge.opposite(), barrier)),
+            )
+        })
+        .collect()
+}
+
+pub fn neighbour(
+    matrix: &Matrix<Node>,
+    coordinates: &Coordinates,
+    edge: &Entry,
+) -> Option<Coordinates> {
+    let (row, col) = *coordinates;
+    let neighbour = match edge {
+        Entry::LEFT => (row, col.checked_sub(1)?),
+        Entry::TOP => (row.checked_sub(1)?, col),
+        Entry::RIGHT => (row, col + 1),
+        Entry::BOTTOM => (row + 1, col),
+    };
+
+    matrix.contains(&neighbour).then_some(neighbour)
+}
+
+pub fn edge_towards(from: &Coordinates, to: &Coordinates) -> Option<Entry> {
+    match (to.0 as i64 - from.0 as i64, to.1 as i64 - from.1 as i64) {
+        (0, -1) => Some(Entry::LEFT),
+        (-1, 0) => Some(Entry::TOP),
+        (0, 1) => Some(Entry::RIGHT),
+        (1, 0) => Some(Entry::BOTTOM),
+        _ => None,
+    }
+}
+
+// opens or closes an edge of a cell and the facing edge of its neighbour
+pub fn set_edge(matrix: &mut Matrix<Node>, coordinates: Coordinates, edge: Entry, open: bool) {
+    if let Some(neighbour) = neighbour(matrix, &coordinates, &edge) {
+        let mut node = matrix[neighbour];
+
+        node[edge.opposite()] = open;
+        matrix.set(neighbour, node);
+    }
+
+    let mut node = matrix[coordinates];
+
+    node[edge] = open;
+    matrix.set(coordinates, node);
+}
+
+// the matrix as seen by a single agent, barriers it may pass are open
+// and hazards weigh on pathfinding once they are known
+pub struct AgentGrid<'a> {
+    matrix: &'a Matrix<Node>,
+    doors: &'a Doors,
+    rules: &'a AgentRules,
+    hazards: Option<&'a Matrix<Hazard>>,
+    footprint: usize,
+    unloaded_open: bool,
+}
+
+impl<'a> AgentGrid<'a> {
+    pub fn new(matrix: &'a Matrix<Node>, doors: &'a Doors, rules: &'a AgentRules) -> Self {
+        Self {
+            matrix,
+            doors,
+            rules,
+            hazards: None,
+            footprint: 1,
+            unloaded_open: false,
+        }
+    }
+
+    pub fn with_hazards(mut self, hazards: &'a Matrix<Hazard>) -> Self {
+        self.hazards = Some(hazards);
+        self
+    }
+
+    // agents larger than a cell stand on the square of this many cells per side below and
+    // right of their cell, the cell is only as open as every cell of the square
+    pub fn with_footprint(mut self, footprint: usize) -> Self {
+        self.footprint = footprint.max(1);
+        self
+    }
+
+    // paths may lead through chunks that aren't loaded, they are planned as open ground and
+    // checked again once the chunks load
+    pub fn with_unloaded_open(mut self) -> Self {
+        self.unloaded_open = true;
+        self
+    }
+
+    fn cell(&self, index: Coordinates) -> &Node {
+        if self.unloaded_open && !self.matrix.is_chunk_loaded(&chunk_of(&index)) {
+            let value: u8 = Node::open().into();
+
+            return &NODES[value as usize];
+        }
+
+        let barriers = self.doors.at(&index);
+
+        if barriers.is_empty() {
+            return &self.matrix[index];
+        }
+
+        let mut node = self.matrix[index];
+
+        for (edge, barrier) in barriers {
+            if self.rules.can_pass(barrier) {
+                node[*edge] = true;
+            }
+        }
+
+        let value: u8 = node.into();
+
+        &NODES[value as usize]
+    }
+}
+
+impl Index<Coordinates> for AgentGrid<'_> {
+    type Output = Node;
+
+    fn index(&self, index: Coordinates) -> &Self::Output {
+        if self.footprint == 1 {
+            return self.cell(index);
+        }
+
+        let rows = index.0..index.0 + self.footprint;
+        let cols = index.1..index.1 + self.footprint;
+
+        if rows.end > self.matrix.rows || cols.end > self.matrix.cols {
+            return &NODES[0];
+        }
+
+        let node = rows
+            .flat_map(|row| cols.clone().map(move |col| (row, col)))
+            .map(|it| self.cell(it))
+            .fold(Node::open(), |node, cell| Node {
+                left: node.left && cell.left,
+                top: node.top && cell.top,
+                right: node.right && cell.right,
+                bottom: node.bottom && cell.bottom,
+            });
+        let value: u8 = node.into();
+
+        &NODES[value as usize]
+    }
+}
+
+impl NodeGrid for AgentGrid<'_> {
+    fn rows(&self) -> usize {
+        self.matrix.rows
+    }
+
+    fn cols(&self) -> usize {
+        self.matrix.cols
+    }
+
+    fn step_cost(&self, index: &Coordinates) -> i32 {
+        self.hazards.map_or(1, |it| it[*index].step_cost())
+    }
+}
+
+// every cell the agent gets to, picking up keys and stepping on switches on the way
+pub fn reachable(
+    matrix: &Matrix<Node>,
+    level: &Level,
+    start: Coordinates,
+    rules: &mut AgentRules,
+) -> HashSet<Coordinates> {
+    let doors = Doors::from_level(level, matrix);
+
+    loop {
+        let grid = AgentGrid::new(matrix, &doors, rules);
+        let mut visited = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(current) = queue.pop_front() {
+            for next in grid.nearest_neighbours(&current).into_iter().flatten() {
+                if visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut found = false;
+
+        for placement in level
+            .placements
+            .iter()
+            .filter(|it| visited.contains(&it.coordinates))
+        {
+            found |= match placement.kind {
+                PlacementKind::Key(color) => rules.keys.insert(color),
+                PlacementKind::Switch(channel) => rules.switches.insert(channel),
+                _ => false,
+            };
+        }
+
+        if !found {
+            return visited;
+        }
+    }
+}
+
+// single cell corridors, the edge is the one a door there would close
+fn corridor_edge(matrix: &Matrix<Node>, coordinates: &Coordinates) -> Option<Entry> {
+    let wall =
+        |edge: Entry| neighbour(matrix, coordinates, &edge).is_none_or(|it| matrix[it].is_wall());
+
+    if matrix[*coordinates].is_wall() {
+        return None;
+    }
+
+    match (
+        wall(Entry::LEFT),
+        wall(Entry::TOP),
+        wall(Entry::RIGHT),
+        wall(Entry::BOTTOM),
+    ) {
+        (true, false, true, false) => Some(Entry::TOP),
+        (false, true, false, true) => Some(Entry::LEFT),
+        _ => None,
+    }
+}
+
+// doors with their keys and gates with their switches, every barrier is only kept
+// when the player can still get everywhere they could get before
+pub fn place_barriers(matrix: &mut Matrix<Node>, level: &mut Level, rng: &mut StdRng) {
+    let start = match level.first_of(PlacementKind::PlayerSpawn) {
+        Some(start) => start,
+        None => return,
+    };
+    let everywhere = reachable(matrix, level, start, &mut AgentRules::player());
+    let taken: HashSet<Coordinates> = level.placements.iter().map(|it| it.coordinates).collect();
+    let mut candidates: Vec<(Coordinates, Entry)> = everywhere
+        .iter()
+        .filter(|it| !taken.contains(it))
+        .filter_map(|it| corridor_edge(matrix, it).map(|edge| (*it, edge)))
+        .collect();
+
+    candidates.sort_unstable_by_key(|it| it.0);
+    candidates.shuffle(rng);
+
+    let mut placed: Vec<Coordinates> = Vec::new();
+    let mut doors = 0;
+    let mut gates = 0;
+
+    for (coordinates, edge) in candidates {
+        if doors == DOORS_PER_FLOOR && gates == GATES_PER_FLOOR {
+            break;
+        }
+
+        if placed
+            .iter()
+            .any(|it| it.0.abs_diff(coordinates.0) + it.1.abs_diff(coordinates.1) < BARRIER_SPACING)
+        {
+            continue;
+        }
+
+        let is_gate = gates < GATES_PER_FLOOR && (doors == DOORS_PER_FLOOR || rng.gen_bool(0.25));
+        let (kind, opener) = match is_gate {
+            true => (
+                PlacementKind::Gate {
+                    edge,
+                    channel: gates as u8,
+                },
+                Some(PlacementKind::Switch(gates as u8)),
+            ),
+            false => {
+                let lock = rng
+                    .gen_bool(0.5)
+                    .then(|| KEY_COLORS[rng.gen_range(0..KEY_COLORS.len())]);
+
+                (
+                    PlacementKind::Door { edge, lock },
+                    lock.map(PlacementKind::Key),
+                )
+            }
+        };
+        let edges = match is_gate {
+            true => vec![edge, edge.opposite()],
+            false => vec![edge],
+        };
+        let before = level.placements.len();
+
+        edges
+            .iter()
+            .for_each(|it| set_edge(matrix, coordinates, *it, false));
+        level.place(kind, coordinates);
+
+        // the key or switch goes somewhere that can be reached with the barrier still closed
+        if let Some(opener) = opener {
+            let mut region: Vec<Coordinates> =
+                reachable(matrix, level, start, &mut AgentRules::player())
+                    .into_iter()
+                    .filter(|it| *it != coordinates && !taken.contains(it))
+                    .filter(|it| level.placements.iter().all(|p| p.coordinates != *it))
+                    .collect();
+
+            region.sort_unstable();
+
+            if let Some(cell) = region.choose(rng) {
+                level.place(opener, *cell);
+            }
+        }
+
+        if reachable(matrix, level, start, &mut AgentRules::player()) == everywhere {
+            placed.push(coordinates);
+
+            match is_gate {
+                true => gates += 1,
+                false => doors += 1,
+            }
+        } else {
+            edges
+                .iter()
+                .for_each(|it| set_edge(matrix, coordinates, *it, true));
+            level.placements.truncate(before);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::chunk::CHUNK_SIZE;
+
+    // a corridor along row 0, closed by a barrier on the right edge of (0, 2)
+    fn corridor(kind: PlacementKind) -> (Matrix<Node>, Level) {
+        let mut matrix = Matrix::new(2, 6, Node::open());
+        let mut level = Level::new(2, 6);
+
+        for col in 0..6 {
+            matrix[(1, col)] = Node::closed();
+        }
+
+        level.place(PlacementKind::PlayerSpawn, (0, 0));
+        level.place(kind, (0, 2));
+
+        for edge in halves(&matrix, &level.placements[1])
+            .into_iter()
+            .filter(|it| it.0 == (0, 2))
+            .map(|it| it.1)
+        {
+            set_edge(&mut matrix, (0, 2), edge, false);
+        }
+
+        (matrix, level)
+    }
+
+    #[test]
+    fn locked_doors_need_their_key() {
+        let door = Barrier::Door(Some(KeyColor::Red));
+        let mut player = AgentRules::player();
+
+        assert!(player.can_pass(&Barrier::Door(None)));
+        assert!(!player.can_pass(&door));
+        assert!(!AgentRules::enemy().can_pass(&Barrier::Door(None)));
+
+        player.keys.insert(KeyColor::Red);
+
+        assert!(player.can_pass(&door));
+        assert!(!player.can_pass(&Barrier::Gate(0)));
+    }
+
+    #[test]
+    fn a_door_blocks_both_ways_until_it_is_removed() {
+        let (matrix, level) = corridor(PlacementKind::Door {
+            edge: Entry::RIGHT,
+            lock: None,
+        });
+        let mut doors = Doors::from_level(&level, &matrix);
+        let barrier = Some((Entry::RIGHT, Barrier::Door(None)));
+
+        assert_eq!(doors.crossed(&(0, 2), &(0, 3)), barrier);
+        assert_eq!(
+            doors.crossed(&(0, 3), &(0, 2)),
+            Some((Entry::LEFT, Barrier::Door(None)))
+        );
+        assert_eq!(doors.crossed(&(0, 1), &(0, 2)), None);
+
+        doors.remove(&matrix, &level.placements[1]);
+
+        assert_eq!(doors.crossed(&(0, 2), &(0, 3)), None);
+        assert!(doors.0.is_empty());
+
+        doors.add(&matrix, &level.placements[1]);
+
+        assert_eq!(doors.crossed(&(0, 2), &(0, 3)), barrier);
+    }
+
+    #[test]
+    fn set_edge_closes_the_facing_edge_too() {
+        let mut matrix = Matrix::new(1, 2, Node::open());
+
+        set_edge(&mut matrix, (0, 0), Entry::RIGHT, false);
+
+        assert!(!matrix[(0, 0)].right && !matrix[(0, 1)].left);
+        assert!(matrix[(0, 0)].left && matrix[(0, 1)].right);
+
+        set_edge(&mut matrix, (0, 1), Entry::LEFT, true);
+
+        assert!(matrix[(0, 0)].right);
+    }
+
+    #[test]
+    fn unloaded_cells_are_open_only_when_asked_for() {
+        let mut matrix = Matrix::unloaded(CHUNK_SIZE, CHUNK_SIZE * 2, Node::closed());
+        let doors = Doors::default();
+        let rules = AgentRules::enemy();
+
+        matrix.load_chunk(&(0, 0));
+
+        let cell = (0, CHUNK_SIZE);
+
+        assert_eq!(
+            AgentGrid::new(&matrix, &doors, &rules)[cell],
+            Node::closed()
+        );
+        assert_eq!(
+            AgentGrid::new(&matrix, &doors, &rules).with_unloaded_open()[cell],
+            Node::open()
+        );
+    }
+
+    #[test]
+    fn keys_on_the_way_open_their_doors() {
+        let (matrix, mut level) = corridor(PlacementKind::Door {
+            edge: Entry::RIGHT,
+            lock: Some(KeyColor::Green),
+        });
+        let mut rules = AgentRules::player();
+
+        assert_eq!(reachable(&matrix, &level, (0, 0), &mut rules).len(), 3);
+
+        level.place(PlacementKind::Key(KeyColor::Green), (0, 1));
+
+        let mut rules = AgentRules::player();
+
+        assert_eq!(reachable(&matrix, &level, (0, 0), &mut rules).len(), 6);
+        assert!(rules.keys.contains(&KeyColor::Green));
+    }
+
+    #[test]
+    fn switches_on_the_way_open_their_gates() {
+        let (matrix, mut level) = corridor(PlacementKind::Gate {
+            edge: Entry::LEFT,
+            channel: 3,
+        });
+
+        assert_eq!(
+            reachable(&matrix, &level, (0, 0), &mut AgentRules::player()).len(),
+            2
+        );
+
+        level.place(PlacementKind::Switch(3), (0, 0));
+
+        let mut rules = AgentRules::player();
+
+        assert_eq!(reachable(&matrix, &level, (0, 0), &mut rules).len(), 6);
+        assert!(rules.switches.contains(&3));
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::Resource;
+
+use super::{
+    chunk::ChunkCoordinates, coordinates::Coordinates, level::Level, matrix::Matrix, node::Node,
+};
+
+// everything about a floor that has to survive while the player is elsewhere
+#[derive(Debug, Clone)]
+pub struct SavedFloor {
+    pub matrix: Matrix<Node>,
+    pub level: Level,
+    pub open_nodes: Vec<Coordinates>,
+    // chunks that were loaded at least once while the player was on the floor
+    pub visited: HashSet<ChunkCoordinates>,
+}
+
+// floors are counted from 1, going down the stairs increases the floor
+#[derive(Resource, Debug)]
+pub struct Dungeon {
+    pub floor: usize,
+    saved: HashMap<usize, SavedFloor>,
+}
+
+impl Default for Dungeon {
+    fn default() -> Self {
+        Self {
+            floor: 1,
+            saved: HashMap::new(),
+        }
+    }
+}
+
+impl Dungeon {
+    pub fn save(&mut self, floor: usize, saved_floor: SavedFloor) {
+        self.saved.insert(floor, saved_floor);
+    }
+
+    pub fn take(&mut self, floor: usize) -> Option<SavedFloor> {
+        self.saved.remove(&floor)
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct EnemySettings {
+    // multiplies the health of every archetype
+    pub health_scale: f32,
+    // seconds per step, the fastest possible plus a random share of the variance
+    pub step_time: f32,
+    pub step_variance: f32,
+}
+
+impl Default for EnemySettings {
+    fn default() -> Self {
+        Self::for_floor(1)
+    }
+}
+
+impl EnemySettings {
+    // every floor down makes enemies tougher and faster
+    pub fn for_floor(floor: usize) -> Self {
+        let depth = floor.saturating_sub(1);
+
+        Self {
+            health_scale: 1. + 0.25 * depth.min(100) as f32,
+            step_time: 0.15 * 0.9_f32.powi(depth as i32),
+            step_variance: 1.5 * 0.85_f32.powi(depth as i32),
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use bevy::prelude::Resource;
+
+use super::{
+    chunk::chunk_of,
+    coordinates::Coordinates,
+    level::{Level, Placement},
+    matrix::Matrix,
+    node::Node,
+};
+
+pub const HISTORY_LIMIT: usize = 100;
+
+// a single undoable step, cells hold (coordinates, before, after),
+// placements only hold the ones the step added and removed
+#[derive(Debug, Clone, Default)]
+pub struct Edit {
+    pub cells: Vec<(Coordinates, Node, Node)>,
+    pub added: Vec<Placement>,
+    pub removed: Vec<Placement>,
+}
+
+impl Edit {
+    // the placements that differ between the two lists, whatever their order
+    pub fn placements(before: &[Placement], after: &[Placement]) -> Self {
+        Self {
+            cells: Vec::new(),
+            added: difference(after, before),
+            removed: difference(before, after),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty() && self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+// every placement of a that is left once each placement of b took away one equal to it
+fn difference(a: &[Placement], b: &[Placement]) -> Vec<Placement> {
+    let mut rest = b.to_vec();
+
+    a.iter()
+        .filter(|it| match rest.iter().position(|other| other == *it) {
+            Some(index) => {
+                rest.swap_remove(index);
+
+                false
+            }
+            None => true,
+        })
+        .copied()
+        .collect()
+}
+
+fn apply(
+    matrix: &mut Matrix<Node>,
+    level: &mut Level,
+    cells: impl Iterator<Item = (Coordinates, Node)>,
+    add: &[Placement],
+    remove: &[Placement],
+) {
+    cells.for_each(|(coordinates, node)| {
+        matrix.set(coordinates, node);
+    });
+
+    for placement in remove {
+        if let Some(index) = level.placements.iter().position(|it| it == placement) {
+            level.placements.remove(index);
+        }
+    }
+
+    level.placements.extend_from_slice(add);
+}
+
+fn unloaded_cells(matrix: &Matrix<Node>, edit: &Edit) -> Result<(), Vec<Coordinates>> {
+    let cells: Vec<Coordinates> = edit
+        .cells
+        .iter()
+        .map(|it| it.0)
+        .filter(|it| !matrix.is_chunk_loaded(&chunk_of(it)))
+        .collect();
+
+    match cells.is_empty() {
+        true => Ok(()),
+        false => Err(cells),
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct EditHistory {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl EditHistory {
+    pub fn push(&mut self, edit: Edit) {
+        if edit.is_empty() {
+            return;
+        }
+
+        self.redo.clear();
+        self.undo.push(edit);
+
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    // Ok(false) when there is nothing to undo, an edit with cells in chunks that are not loaded
+    // stays where it is and those cells come back as the error
+    pub fn undo(
+        &mut self,
+        matrix: &mut Matrix<Node>,
+        level: &mut Level,
+    ) -> Result<bool, Vec<Coordinates>> {
+        let edit = match self.undo.last() {
+            Some(edit) => edit,
+            None => return Ok(false),
+        };
+
+        unloaded_cells(matrix, edit)?;
+        apply(
+            matrix,
+            level,
+            edit.cells.iter().rev().map(|it| (it.0, it.1)),
+            &edit.removed,
+            &edit.added,
+        );
+
+        self.redo.extend(self.undo.pop());
+
+        Ok(true)
+    }
+
+    pub fn redo(
+        &mut self,
+        matrix: &mut Matrix<Node>,
+        level: &mut Level,
+    ) -> Result<bool, Vec<Coordinates>> {
+        let edit = match self.redo.last() {
+            Some(edit) => edit,
+            None => return Ok(false),
+        };
+
+        unloaded_cells(matrix, edit)?;
+        apply(
+            matrix,
+            level,
+            edit.cells.iter().map(|it| (it.0, it.2)),
+            &edit.added,
+            &edit.removed,
+        );
+
+        self.undo.extend(self.redo.pop());
+
+        Ok(true)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{chunk::CHUNK_SIZE, level::PlacementKind};
+
+    fn stroke(matrix: &mut Matrix<Node>, cells: &[Coordinates]) -> Edit {
+        Edit {
+            cells: cells
+                .iter()
+                .map(|it| {
+                    let before = matrix[*it];
+
+                    matrix.set(*it, Node::closed());
+
+                    (*it, before, Node::closed())
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_cells() {
+        let mut matrix = Matrix::new(4, 4, Node::open());
+        let mut level = Level::new(4, 4);
+        let mut history = EditHistory::default();
+        let edit = stroke(&mut matrix, &[(0, 0), (1, 1)]);
+
+        history.push(edit);
+
+        assert_eq!(history.undo(&mut matrix, &mut level), Ok(true));
+        assert!(!matrix[(0, 0)].is_wall() && !matrix[(1, 1)].is_wall());
+        assert_eq!(history.undo(&mut matrix, &mut level), Ok(false));
+        assert_eq!(history.redo(&mut matrix, &mut level), Ok(true));
+        assert!(matrix[(0, 0)].is_wall() && matrix[(1, 1)].is_wall());
+        assert_eq!(history.redo(&mut matrix, &mut level), Ok(false));
+    }
+
+    #[test]
+    fn placements_are_undone_by_their_difference_only() {
+        let mut matrix = Matrix::new(4, 4, Node::open());
+        let mut level = Level::new(4, 4);
+        let mut history = EditHistory::default();
+
+        level.place(PlacementKind::Exit, (0, 0));
+        level.place(PlacementKind::SpeedPowerUp, (1, 1));
+
+        let before = level.placements.clone();
+
+        level.placements.retain(|it| it.kind != PlacementKind::Exit);
+        level.place(PlacementKind::Exit, (3, 3));
+        history.push(Edit::placements(&before, &level.placements));
+
+        // picked up after the edit, the undo must not bring it back
+        level.remove(PlacementKind::SpeedPowerUp, (1, 1));
+
+        assert_eq!(history.undo(&mut matrix, &mut level), Ok(true));
+        assert_eq!(level.first_of(PlacementKind::Exit), Some((0, 0)));
+        assert_eq!(level.placements.len(), 1);
+        assert_eq!(history.redo(&mut matrix, &mut level), Ok(true));
+        assert_eq!(level.first_of(PlacementKind::Exit), Some((3, 3)));
+        assert_eq!(level.placements.len(), 1);
+    }
+
+    #[test]
+    fn empty_edits_are_not_kept_and_new_ones_drop_the_redo() {
+        let mut matrix = Matrix::new(4, 4, Node::open());
+        let mut level = Level::new(4, 4);
+        let mut history = EditHistory::default();
+
+        history.push(Edit::default());
+        history.push(Edit::placements(&level.placements, &level.placements));
+
+        assert_eq!(history.undo(&mut matrix, &mut level), Ok(false));
+
+        let edit = stroke(&mut matrix, &[(0, 0)]);
+
+        history.push(edit);
+        history.undo(&mut matrix, &mut level).unwrap();
+
+        let edit = stroke(&mut matrix, &[(2, 2)]);
+
+        history.push(edit);
+
+        assert_eq!(history.redo(&mut matrix, &mut level), Ok(false));
+    }
+
+    #[test]
+    fn keeps_at_most_the_limit() {
+        let mut matrix = Matrix::new(1, HISTORY_LIMIT + 1, Node::open());
+        let mut level = Level::new(1, HISTORY_LIMIT + 1);
+        let mut history = EditHistory::default();
+
+        for col in 0..=HISTORY_LIMIT {
+            let edit = stroke(&mut matrix, &[(0, col)]);
+
+            history.push(edit);
+        }
+
+        while history.undo(&mut matrix, &mut level) == Ok(true) {}
+
+        assert!(matrix[(0, 0)].is_wall());
+        assert!(!matrix[(0, 1)].is_wall());
+    }
+
+    #[test]
+    fn edits_in_unloaded_chunks_stay_until_they_are_loaded() {
+        let mut matrix = Matrix::new(CHUNK_SIZE * 2, CHUNK_SIZE, Node::open());
+        let mut level = Level::new(CHUNK_SIZE * 2, CHUNK_SIZE);
+        let mut history = EditHistory::default();
+        let edit = stroke(&mut matrix, &[(0, 0), (CHUNK_SIZE, 0)]);
+
+        history.push(edit);
+        matrix.unload_chunk(&(1, 0));
+
+        assert_eq!(
+            history.undo(&mut matrix, &mut level),
+            Err(vec![(CHUNK_SIZE, 0)])
+        );
+        assert!(matrix[(0, 0)].is_wall());
+
+        matrix.load_chunk(&(1, 0));
+
+        assert_eq!(history.undo(&mut matrix, &mut level), Ok(true));
+        assert!(!matrix[(0, 0)].is_wall());
+    }
+}
@@ -1,25 +1,35 @@
 use std::fs::File;
 use std::io::{Read, Write};
 
+use super::hazard::Hazard;
+use super::level::{Decoration, Level, Placement, PlacementKind};
 use super::matrix::Matrix;
 use super::node::Node;
 
+// every file starts with these, then the version of the layout that follows
+const MAGIC: &[u8] = b"LVL";
+// bumped whenever the layout changes, files of older versions are still read
+const VERSION: u8 = 2;
+
+// cells hold the node in the low nibble and the floor variant in the high nibble,
+// decorations hold the decoration in the low 2 bits and the wall damage above,
+// hazards come last, version 1 files may end before them
 #[derive(Debug)]
 pub struct EncodedMatrix {
     pub cells: Vec<u8>,
+    pub decorations: Vec<u8>,
+    pub placements: Vec<Placement>,
+    pub hazards: Vec<u8>,
     pub rows: usize,
     pub cols: usize,
 }
 
 impl EncodedMatrix {
     pub fn to_file(&self, file_name: &str) -> std::io::Result<()> {
-        let mut data = vec![self.rows as u8, self.cols as u8];
-
-        data.append(&mut self.cells.clone());
-
         let mut e = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
 
-        e.write_all(&data).expect("could not write to file");
+        e.write_all(&self.encode())
+            .expect("could not write to file");
 
         let data = e.finish().expect("could not zip bytes");
 
@@ -34,45 +44,326 @@ impl EncodedMatrix {
         Ok(())
     }
 
-    pub fn from_file(file_name: &str) -> Self {
-        let raw = std::fs::read(file_name).expect("could not read from file");
+    fn encode(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+
+        data.push(VERSION);
+        data.extend_from_slice(&(self.rows as u16).to_be_bytes());
+        data.extend_from_slice(&(self.cols as u16).to_be_bytes());
+        data.append(&mut self.cells.clone());
+        data.append(&mut self.decorations.clone());
+        data.extend_from_slice(&(self.placements.len() as u32).to_be_bytes());
+
+        self.placements.iter().for_each(|it| {
+            data.push(it.kind.into());
+            data.extend_from_slice(&(it.coordinates.0 as u16).to_be_bytes());
+            data.extend_from_slice(&(it.coordinates.1 as u16).to_be_bytes());
+        });
+
+        data.append(&mut self.hazards.clone());
+
+        data
+    }
+
+    pub fn from_file(file_name: &str) -> std::io::Result<Self> {
+        let raw = std::fs::read(file_name)?;
 
         let mut z = flate2::read::ZlibDecoder::new(&raw[..]);
         let mut v: Vec<u8> = Vec::new();
 
-        z.read_to_end(&mut v).expect("could not read to vector");
+        z.read_to_end(&mut v)?;
 
-        let rows = v.remove(0);
-        let cols = v.remove(0);
+        Self::decode(&v).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "{} is not a level of version {} or older",
+                    file_name, VERSION
+                ),
+            )
+        })
+    }
 
-        Self {
-            rows: rows as usize,
-            cols: cols as usize,
-            cells: v,
+    fn decode(v: &[u8]) -> Option<Self> {
+        let version = *v.get(MAGIC.len())?;
+
+        if v.get(..MAGIC.len())? != MAGIC || !(1..=VERSION).contains(&version) {
+            return None;
         }
+
+        let mut pos = MAGIC.len() + 1;
+        let rows = u16::from_be_bytes(v.get(pos..pos + 2)?.try_into().ok()?) as usize;
+        let cols = u16::from_be_bytes(v.get(pos + 2..pos + 4)?.try_into().ok()?) as usize;
+        let len = rows * cols;
+
+        pos += 4;
+
+        let cells = v.get(pos..pos + len)?.to_vec();
+
+        pos += len;
+
+        let decorations = v.get(pos..pos + len)?.to_vec();
+
+        pos += len;
+
+        let count = u32::from_be_bytes(v.get(pos..pos + 4)?.try_into().ok()?) as usize;
+
+        pos += 4;
+
+        let placements = v
+            .get(pos..pos + count * 5)?
+            .chunks_exact(5)
+            .filter_map(|it| {
+                PlacementKind::try_from(it[0]).ok().map(|kind| Placement {
+                    kind,
+                    coordinates: (
+                        u16::from_be_bytes([it[1], it[2]]) as usize,
+                        u16::from_be_bytes([it[3], it[4]]) as usize,
+                    ),
+                })
+            })
+            .collect();
+
+        pos += count * 5;
+
+        let hazards = match (version, v.get(pos..pos + len)) {
+            (_, Some(hazards)) => hazards.to_vec(),
+            (1, None) => vec![Hazard::None.into(); len],
+            _ => return None,
+        };
+
+        Some(Self {
+            rows,
+            cols,
+            cells,
+            decorations,
+            placements,
+            hazards,
+        })
     }
 }
 
 impl From<EncodedMatrix> for Matrix<Node> {
     fn from(encoded: EncodedMatrix) -> Self {
-        Matrix {
-            vec: encoded
-                .cells
-                .iter()
-                .map(|it| it.to_owned().into())
-                .collect(),
-            rows: encoded.rows,
-            cols: encoded.cols,
-        }
+        Matrix::from_cells(
+            encoded.rows,
+            encoded.cols,
+            Node::closed(),
+            encoded.cells.iter().map(|it| (it & 0b1111).into()),
+        )
+    }
+}
+
+impl From<EncodedMatrix> for (Matrix<Node>, Level) {
+    fn from(encoded: EncodedMatrix) -> Self {
+        let (rows, cols) = (encoded.rows, encoded.cols);
+        let level = Level {
+            floor: Matrix::from_cells(rows, cols, 0, encoded.cells.iter().map(|it| it >> 4)),
+            decoration: Matrix::from_cells(
+                rows,
+                cols,
+                Decoration::None,
+                encoded
+                    .decorations
+                    .iter()
+                    .map(|it| Decoration::from(it & 0b11)),
+            ),
+            wall_damage: Matrix::from_cells(
+                rows,
+                cols,
+                0,
+                encoded.decorations.iter().map(|it| it >> 2),
+            ),
+            hazard: Matrix::from_cells(
+                rows,
+                cols,
+                Hazard::None,
+                encoded.hazards.iter().map(|it| Hazard::from(*it)),
+            ),
+            placements: encoded.placements.clone(),
+        };
+
+        (encoded.into(), level)
     }
 }
 
 impl Into<EncodedMatrix> for Matrix<Node> {
     fn into(self) -> EncodedMatrix {
+        let len = self.rows * self.cols;
+
         EncodedMatrix {
-            cells: self.vec.iter().map(|it| it.to_owned().into()).collect(),
+            cells: self.iter().map(|it| it.to_owned().into()).collect(),
+            decorations: vec![Decoration::None.into(); len],
+            placements: Vec::new(),
+            hazards: vec![Hazard::None.into(); len],
             rows: self.rows,
             cols: self.cols,
         }
     }
 }
+
+impl From<(&Matrix<Node>, &Level)> for EncodedMatrix {
+    fn from((matrix, level): (&Matrix<Node>, &Level)) -> Self {
+        EncodedMatrix {
+            cells: matrix
+                .iter()
+                .zip(level.floor.iter())
+                .map(|(node, floor)| {
+                    let node: u8 = node.to_owned().into();
+
+                    node | (floor << 4)
+                })
+                .collect(),
+            decorations: level
+                .decoration
+                .iter()
+                .zip(level.wall_damage.iter())
+                .map(|(decoration, damage)| {
+                    let decoration: u8 = (*decoration).into();
+
+                    decoration | (damage << 2)
+                })
+                .collect(),
+            placements: level.placements.clone(),
+            hazards: level.hazard.iter().map(|it| (*it).into()).collect(),
+            rows: matrix.rows,
+            cols: matrix.cols,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{door::KeyColor, node::Entry};
+
+    fn level() -> (Matrix<Node>, Level) {
+        let mut matrix = Matrix::new(20, 18, Node::open());
+        let mut level = Level::new(20, 18);
+
+        matrix[(0, 0)] = Node::closed();
+        matrix[(3, 4)].right = false;
+        level.floor[(19, 17)] = 8;
+        level.decoration[(5, 6)] = Decoration::Rubble;
+        level.wall_damage[(0, 0)] = 59;
+        level.hazard[(7, 7)] = Hazard::Teleporter(3);
+        level.hazard[(8, 1)] = Hazard::Pit;
+        level.place(PlacementKind::PlayerSpawn, (1, 1));
+        level.place(PlacementKind::Exit, (19, 17));
+        level.place(PlacementKind::Key(KeyColor::Yellow), (2, 9));
+        level.place(
+            PlacementKind::Door {
+                edge: Entry::RIGHT,
+                lock: Some(KeyColor::Blue),
+            },
+            (3, 4),
+        );
+        level.place(
+            PlacementKind::Door {
+                edge: Entry::BOTTOM,
+                lock: None,
+            },
+            (10, 2),
+        );
+        level.place(
+            PlacementKind::Gate {
+                edge: Entry::TOP,
+                channel: 7,
+            },
+            (12, 12),
+        );
+        level.place(PlacementKind::Switch(7), (14, 3));
+
+        (matrix, level)
+    }
+
+    #[test]
+    fn every_layer_survives_a_round_trip() {
+        let (matrix, level) = level();
+        let encoded = EncodedMatrix::decode(&EncodedMatrix::from((&matrix, &level)).encode())
+            .expect("a level that was just encoded");
+        let (decoded_matrix, decoded_level): (Matrix<Node>, Level) = encoded.into();
+
+        assert!(matrix.iter().eq(decoded_matrix.iter()));
+        assert!(level.floor.iter().eq(decoded_level.floor.iter()));
+        assert!(level.decoration.iter().eq(decoded_level.decoration.iter()));
+        assert!(level
+            .wall_damage
+            .iter()
+            .eq(decoded_level.wall_damage.iter()));
+        assert!(level.hazard.iter().eq(decoded_level.hazard.iter()));
+        assert_eq!(decoded_level.placements, level.placements);
+    }
+
+    #[test]
+    fn gates_come_back_on_the_left_or_top_edge() {
+        let mut level = Level::new(2, 2);
+
+        level.place(
+            PlacementKind::Gate {
+                edge: Entry::RIGHT,
+                channel: 2,
+            },
+            (1, 1),
+        );
+        level.place(
+            PlacementKind::Gate {
+                edge: Entry::BOTTOM,
+                channel: 0,
+            },
+            (0, 1),
+        );
+
+        let encoded = EncodedMatrix::from((&Matrix::new(2, 2, Node::open()), &level));
+        let decoded = EncodedMatrix::decode(&encoded.encode()).expect("a level");
+
+        assert_eq!(
+            decoded
+                .placements
+                .iter()
+                .map(|it| it.kind)
+                .collect::<Vec<_>>(),
+            vec![
+                PlacementKind::Gate {
+                    edge: Entry::LEFT,
+                    channel: 2
+                },
+                PlacementKind::Gate {
+                    edge: Entry::TOP,
+                    channel: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn version_1_files_load_without_hazards() {
+        let (matrix, level) = level();
+        let mut data = EncodedMatrix::from((&matrix, &level)).encode();
+
+        data[MAGIC.len()] = 1;
+        data.truncate(data.len() - matrix.rows * matrix.cols);
+
+        let decoded = EncodedMatrix::decode(&data).expect("a version 1 level");
+
+        assert!(decoded
+            .hazards
+            .iter()
+            .all(|it| Hazard::from(*it) == Hazard::None));
+        assert_eq!(decoded.placements, level.placements);
+    }
+
+    #[test]
+    fn truncated_and_unknown_files_are_rejected() {
+        let (matrix, level) = level();
+        let data = EncodedMatrix::from((&matrix, &level)).encode();
+        let mut newer = data.clone();
+
+        newer[MAGIC.len()] = VERSION + 1;
+
+        assert!(EncodedMatrix::decode(&data[..data.len() - 1]).is_none());
+        assert!(EncodedMatrix::decode(&data[..MAGIC.len() + 3]).is_none());
+        assert!(EncodedMatrix::decode(&newer).is_none());
+        assert!(EncodedMatrix::decode(b"XYZ").is_none());
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+
+use rand::prelude::*;
+
+use super::{coordinates::Coordinates, level::Level, matrix::Matrix, node::Node};
+
+// how many of each hazard a generated floor gets at most
+const SPIKES_PER_FLOOR: usize = 30;
+const MUD_PATCHES_PER_FLOOR: usize = 12;
+const PITS_PER_FLOOR: usize = 10;
+const TELEPORTER_PAIRS_PER_FLOOR: u8 = 3;
+// teleporters closer than this in cells are not worth a pair
+const TELEPORTER_DISTANCE: usize = 20;
+// health lost for every step onto spikes, and every second spent standing on them
+pub const SPIKE_DAMAGE: u16 = 10;
+// health lost falling into a pit, enemies never climb back out
+pub const PIT_DAMAGE: u16 = 25;
+
+// the pair id of teleporters has to stay below 240 to fit the encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hazard {
+    None,
+    Spikes,
+    Mud,
+    Pit,
+    Teleporter(u8),
+}
+
+impl Hazard {
+    // pathfinding weight of entering the cell, enemies walk around hazards when they can
+    pub fn step_cost(&self) -> i32 {
+        match self {
+            Hazard::None | Hazard::Teleporter(_) => 1,
+            Hazard::Mud => 3,
+            Hazard::Spikes => 6,
+            Hazard::Pit => 40,
+        }
+    }
+
+    // share of the usual speed when stepping out of the cell
+    pub fn pace(&self) -> f32 {
+        match self {
+            Hazard::Mud => 0.4,
+            _ => 1.,
+        }
+    }
+}
+
+impl From<u8> for Hazard {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Hazard::Spikes,
+            2 => Hazard::Mud,
+            3 => Hazard::Pit,
+            16.. => Hazard::Teleporter(value - 16),
+            _ => Hazard::None,
+        }
+    }
+}
+
+impl From<Hazard> for u8 {
+    fn from(value: Hazard) -> Self {
+        match value {
+            Hazard::None => 0,
+            Hazard::Spikes => 1,
+            Hazard::Mud => 2,
+            Hazard::Pit => 3,
+            Hazard::Teleporter(pair) => 16 + pair.min(239),
+        }
+    }
+}
+
+impl Level {
+    // the other end of the pair, nothing for a single teleporter
+    pub fn teleporter_exit(&self, coordinates: Coordinates) -> Option<Coordinates> {
+        let pair = match self.hazard[coordinates] {
+            Hazard::Teleporter(pair) => pair,
+            _ => return None,
+        };
+
+        (0..self.hazard.rows)
+            .flat_map(|row| (0..self.hazard.cols).map(move |col| (row, col)))
+            .find(|it| *it != coordinates && self.hazard[*it] == Hazard::Teleporter(pair))
+    }
+}
+
+// pits only go into rooms, so that there always is a way around them
+pub fn place_hazards(matrix: &Matrix<Node>, level: &mut Level, rng: &mut StdRng) {
+    let taken: HashSet<Coordinates> = level.placements.iter().map(|it| it.coordinates).collect();
+    let mut free: Vec<Coordinates> = (0..matrix.rows)
+        .flat_map(|row| (0..matrix.cols).map(move |col| (row, col)))
+        .filter(|it| !matrix[*it].is_wall() && !taken.contains(it))
+        .collect();
+
+    if free.is_empty() {
+        return;
+    }
+
+    free.shuffle(rng);
+
+    let around = |coordinates: Coordinates| {
+        let (row, col) = coordinates;
+
+        (row.saturating_sub(1)..=row + 1)
+            .flat_map(move |r| (col.saturating_sub(1)..=col + 1).map(move |c| (r, c)))
+            .filter(move |it| *it != coordinates)
+    };
+
+    for coordinates in free.iter().take(SPIKES_PER_FLOOR) {
+        level.hazard[*coordinates] = Hazard::Spikes;
+    }
+
+    for center in free
+        .iter()
+        .skip(SPIKES_PER_FLOOR)
+        .take(MUD_PATCHES_PER_FLOOR)
+    {
+        for coordinates in around(*center).chain([*center]) {
+            if matrix.contains(&coordinates)
+                && !matrix[coordinates].is_wall()
+                && !taken.contains(&coordinates)
+                && level.hazard[coordinates] == Hazard::None
+                && rng.gen_bool(0.7)
+            {
+                level.hazard[coordinates] = Hazard::Mud;
+            }
+        }
+    }
+
+    let mut pits = 0;
+
+    for coordinates in &free {
+        if pits == PITS_PER_FLOOR {
+            break;
+        }
+
+        let open_room = around(*coordinates).all(|it| {
+            matrix.contains(&it)
+                && !matrix[it].is_wall()
+                && !taken.contains(&it)
+                && level.hazard[it] != Hazard::Pit
+        });
+
+        if open_room && level.hazard[*coordinates] == Hazard::None {
+            level.hazard[*coordinates] = Hazard::Pit;
+            pits += 1;
+        }
+    }
+
+    let ends: Vec<Coordinates> = free
+        .iter()
+        .filter(|it| level.hazard[**it] == Hazard::None)
+        .copied()
+        .collect();
+    let mut ends = ends.into_iter();
+
+    for pair in 0..TELEPORTER_PAIRS_PER_FLOOR {
+        let from = match ends.next() {
+            Some(from) => from,
+            None => break,
+        };
+        let to = ends
+            .by_ref()
+            .find(|it| it.0.abs_diff(from.0) + it.1.abs_diff(from.1) >= TELEPORTER_DISTANCE);
+
+        if let Some(to) = to {
+            level.hazard[from] = Hazard::Teleporter(pair);
+            level.hazard[to] = Hazard::Teleporter(pair);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::Resource;
+
+use super::{
+    coordinates::Coordinates,
+    door::{KeyColor, KEY_COLORS},
+    hazard::Hazard,
+    matrix::Matrix,
+    node::Entry,
+};
+
+pub const FLOOR_VARIANTS: u8 = 9;
+// damage a wall takes before it breaks, it has to fit the 6 bits left next to the decoration
+pub const WALL_HEALTH: u8 = 60;
+pub const CRACK_STAGES: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoration {
+    None,
+    Moss,
+    Blood,
+    Rubble,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementKind {
+    PlayerSpawn,
+    EnemySpawn,
+    SpeedPowerUp,
+    ProjectilePowerUp,
+    // the stairs down, placed by mapgen's DistantExit
+    Exit,
+    // the stairs back up, every floor below the first has them at the player spawn
+    Entrance,
+    Key(KeyColor),
+    // closes one edge of its cell until the player opens it, with the key of the color if locked
+    Door { edge: Entry, lock: Option<KeyColor> },
+    // closes an edge and its opposite, toggled by every switch of the same channel
+    Gate { edge: Entry, channel: u8 },
+    Switch(u8),
+}
+
+// order of the encoded edges, gates close a whole axis and come back as left or top
+const EDGES: [Entry; 4] = [Entry::LEFT, Entry::TOP, Entry::RIGHT, Entry::BOTTOM];
+pub const GATE_CHANNELS: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub kind: PlacementKind,
+    pub coordinates: Coordinates,
+}
+
+// the passability layer remains the Matrix<Node> resource,
+// the Level holds everything that is stacked on top of it
+#[derive(Resource, Debug, Clone)]
+pub struct Level {
+    pub floor: Matrix<u8>,
+    pub decoration: Matrix<Decoration>,
+    // damage taken by the wall in each cell, 0 for untouched walls and for floor
+    pub wall_damage: Matrix<u8>,
+    pub hazard: Matrix<Hazard>,
+    pub placements: Vec<Placement>,
+}
+
+impl Level {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            floor: Matrix::new(rows, cols, 0),
+            decoration: Matrix::new(rows, cols, Decoration::None),
+            wall_damage: Matrix::new(rows, cols, 0),
+            hazard: Matrix::new(rows, cols, Hazard::None),
+            placements: Vec::new(),
+        }
+    }
+
+    // every layer is loaded chunk by chunk, the placements are always all there
+    pub fn unloaded(rows: usize, cols: usize) -> Self {
+        Self {
+            floor: Matrix::unloaded(rows, cols, 0),
+            decoration: Matrix::unloaded(rows, cols, Decoration::None),
+            wall_damage: Matrix::unloaded(rows, cols, 0),
+            hazard: Matrix::unloaded(rows, cols, Hazard::None),
+            placements: Vec::new(),
+        }
+    }
+
+    pub fn place(&mut self, kind: PlacementKind, coordinates: Coordinates) {
+        self.placements.push(Placement { kind, coordinates });
+    }
+
+    pub fn placements_of(&self, kind: PlacementKind) -> impl Iterator<Item = Coordinates> + '_ {
+        self.placements
+            .iter()
+            .filter(move |it| it.kind == kind)
+            .map(|it| it.coordinates)
+    }
+
+    pub fn first_of(&self, kind: PlacementKind) -> Option<Coordinates> {
+        self.placements_of(kind).next()
+    }
+
+    // true when the wall breaks, which leaves no damage behind for the cell
+    pub fn damage_wall(&mut self, coordinates: Coordinates, damage: u16) -> bool {
+        let total = self.wall_damage[coordinates] as u16 + damage;
+        let broken = total >= WALL_HEALTH as u16;
+
+        self.wall_damage.set(
+            coordinates,
+            match broken {
+                true => 0,
+                false => total as u8,
+            },
+        );
+
+        broken
+    }
+
+    // 0 for an intact wall, up to CRACK_STAGES just before it breaks
+    pub fn crack_stage(&self, coordinates: Coordinates) -> u8 {
+        match self.wall_damage[coordinates] {
+            0 => 0,
+            damage => (1 + damage as u16 * CRACK_STAGES as u16 / WALL_HEALTH as u16)
+                .min(CRACK_STAGES as u16) as u8,
+        }
+    }
+
+    pub fn remove(&mut self, kind: PlacementKind, coordinates: Coordinates) {
+        self.placements
+            .retain(|it| it.kind != kind || it.coordinates != coordinates);
+    }
+}
+
+impl From<u8> for Decoration {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Decoration::Moss,
+            2 => Decoration::Blood,
+            3 => Decoration::Rubble,
+            _ => Decoration::None,
+        }
+    }
+}
+
+impl From<Decoration> for u8 {
+    fn from(value: Decoration) -> Self {
+        match value {
+            Decoration::None => 0,
+            Decoration::Moss => 1,
+            Decoration::Blood => 2,
+            Decoration::Rubble => 3,
+        }
+    }
+}
+
+impl TryFrom<u8> for PlacementKind {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PlacementKind::PlayerSpawn),
+            1 => Ok(PlacementKind::EnemySpawn),
+            2 => Ok(PlacementKind::SpeedPowerUp),
+            3 => Ok(PlacementKind::ProjectilePowerUp),
+            4 => Ok(PlacementKind::Exit),
+            5 => Ok(PlacementKind::Entrance),
+            8..=11 => Ok(PlacementKind::Key(KEY_COLORS[value as usize - 8])),
+            16..=35 => Ok(PlacementKind::Door {
+                edge: EDGES[(value as usize - 16) % 4],
+                lock: match (value as usize - 16) / 4 {
+                    0 => None,
+                    lock => Some(KEY_COLORS[lock - 1]),
+                },
+            }),
+            48..=63 => Ok(PlacementKind::Gate {
+                edge: EDGES[(value as usize - 48) / GATE_CHANNELS as usize],
+                channel: (value - 48) % GATE_CHANNELS,
+            }),
+            64..=71 => Ok(PlacementKind::Switch(value - 64)),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<PlacementKind> for u8 {
+    fn from(value: PlacementKind) -> Self {
+        match value {
+            PlacementKind::PlayerSpawn => 0,
+            PlacementKind::EnemySpawn => 1,
+            PlacementKind::SpeedPowerUp => 2,
+            PlacementKind::ProjectilePowerUp => 3,
+            PlacementKind::Exit => 4,
+            PlacementKind::Entrance => 5,
+            PlacementKind::Key(color) => 8 + u8::from(color),
+            PlacementKind::Door { edge, lock } => {
+                16 + lock.map_or(0, |it| u8::from(it) + 1) * 4 + edge_index(edge)
+            }
+            PlacementKind::Gate { edge, channel } => {
+                48 + edge_index(edge) % 2 * GATE_CHANNELS + channel % GATE_CHANNELS
+            }
+            PlacementKind::Switch(channel) => 64 + channel % GATE_CHANNELS,
+        }
+    }
+}
+
+fn edge_index(edge: Entry) -> u8 {
+    EDGES.iter().position(|it| *it == edge).unwrap_or(0) as u8
+}
@@ -0,0 +1,184 @@
+use bevy::prelude::{warn, Resource};
+use mapgen::{
+    drunkard::DrunkSpawnMode, AreaStartingPosition, BspInterior, BspRooms, CellularAutomata,
+    CullUnreachable, DistantExit, DrunkardsWalk, Map, MapBuilder, MazeBuilder, NearestCorridors,
+    NoiseGenerator, SimpleRooms, Symmetry, VoronoiHive, XStart, YStart,
+};
+use rand::rngs::StdRng;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum DrunkardSpawn {
+    StartingPoint,
+    Random,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum MapSymmetry {
+    None,
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum StartX {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum StartY {
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Generator {
+    BspRooms {
+        corridors: bool,
+    },
+    BspInterior,
+    SimpleRooms {
+        corridors: bool,
+    },
+    DrunkardsWalk {
+        spawn: DrunkardSpawn,
+        lifetime: i32,
+        floor_percent: f32,
+        brush_size: usize,
+        symmetry: MapSymmetry,
+    },
+    Maze,
+    Voronoi,
+    CellularCaves {
+        noise: f32,
+        smoothing_passes: u32,
+    },
+}
+
+#[derive(Resource, Deserialize, Debug, Clone)]
+pub struct MapGenConfig {
+    pub generator: Generator,
+    pub start: (StartX, StartY),
+    pub cull_unreachable: bool,
+    pub distant_exit: bool,
+}
+
+impl Default for MapGenConfig {
+    fn default() -> Self {
+        Self {
+            generator: Generator::CellularCaves {
+                noise: 0.5,
+                smoothing_passes: 1,
+            },
+            start: (StartX::Left, StartY::Bottom),
+            cull_unreachable: true,
+            distant_exit: true,
+        }
+    }
+}
+
+impl MapGenConfig {
+    pub fn from_file(file_name: &str) -> Self {
+        std::fs::read_to_string(file_name)
+            .map_err(|error| error.to_string())
+            .and_then(|raw| ron::from_str(&raw).map_err(|error| error.to_string()))
+            .unwrap_or_else(|error| {
+                warn!(
+                    "could not load {}, using the default map: {}",
+                    file_name, error
+                );
+
+                Self::default()
+            })
+    }
+
+    pub fn build(&self, rows: usize, cols: usize, rng: &mut StdRng) -> Map {
+        let mut builder = MapBuilder::new(cols, rows);
+
+        match &self.generator {
+            Generator::BspRooms { corridors } => {
+                builder.with(BspRooms::new());
+
+                if *corridors {
+                    builder.with(NearestCorridors::new());
+                }
+            }
+            Generator::BspInterior => {
+                builder.with(BspInterior::new());
+            }
+            Generator::SimpleRooms { corridors } => {
+                builder.with(SimpleRooms::new());
+
+                if *corridors {
+                    builder.with(NearestCorridors::new());
+                }
+            }
+            Generator::DrunkardsWalk {
+                spawn,
+                lifetime,
+                floor_percent,
+                brush_size,
+                symmetry,
+            } => {
+                builder.with(DrunkardsWalk::new(
+                    match spawn {
+                        DrunkardSpawn::StartingPoint => DrunkSpawnMode::StartingPoint,
+                        DrunkardSpawn::Random => DrunkSpawnMode::Random,
+                    },
+                    *lifetime,
+                    *floor_percent,
+                    *brush_size,
+                    match symmetry {
+                        MapSymmetry::None => Symmetry::None,
+                        MapSymmetry::Horizontal => Symmetry::Horizontal,
+                        MapSymmetry::Vertical => Symmetry::Vertical,
+                        MapSymmetry::Both => Symmetry::Both,
+                    },
+                ));
+            }
+            Generator::Maze => {
+                builder.with(MazeBuilder::new());
+            }
+            Generator::Voronoi => {
+                builder.with(VoronoiHive::new());
+            }
+            Generator::CellularCaves {
+                noise,
+                smoothing_passes,
+            } => {
+                builder.with(NoiseGenerator::new(*noise));
+
+                (0..*smoothing_passes).for_each(|_| {
+                    builder.with(CellularAutomata::new());
+                });
+            }
+        }
+
+        builder.with(AreaStartingPosition::new(
+            match self.start.0 {
+                StartX::Left => XStart::LEFT,
+                StartX::Center => XStart::CENTER,
+                StartX::Right => XStart::RIGHT,
+            },
+            match self.start.1 {
+                StartY::Top => YStart::TOP,
+                StartY::Center => YStart::CENTER,
+                StartY::Bottom => YStart::BOTTOM,
+            },
+        ));
+
+        if self.cull_unreachable {
+            builder.with(CullUnreachable::new());
+        }
+
+        if self.distant_exit {
+            builder.with(DistantExit::new());
+        }
+
+        builder.build_with_rng(rng)
+    }
+}
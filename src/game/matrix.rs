@@ -1,14 +1,73 @@
+use std::collections::VecDeque;
 use std::ops::{Index, IndexMut};
 
 use bevy::prelude::Resource;
 
+use super::chunk::{ChunkCoordinates, CHUNK_SIZE};
 use super::coordinates::{Coordinates, CreateCoordinates};
 
+const CHANGE_LOG_CAPACITY: usize = 4096;
+
+// cells are kept by chunk, row by row within the chunk, so that a chunk can be
+// dropped from memory on its own
 #[derive(Resource, Debug, Clone)]
 pub struct Matrix<T> {
-    pub vec: Vec<T>,
+    chunks: Vec<Option<Vec<T>>>,
+    default: T,
     pub rows: usize,
     pub cols: usize,
+    pub changes: ChangeLog,
+}
+
+// every write through set that changes a cell bumps the generation, consumers remember
+// the last generation they have seen and ask for whatever happened after it
+#[derive(Debug, Clone, Default)]
+pub struct ChangeLog {
+    generation: u64,
+    // the log holds every change after this generation
+    complete_since: u64,
+    cells: VecDeque<(u64, Coordinates)>,
+}
+
+impl ChangeLog {
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn record(&mut self, coordinates: Coordinates) {
+        self.generation += 1;
+
+        if self.cells.len() == CHANGE_LOG_CAPACITY {
+            if let Some((oldest, _)) = self.cells.pop_front() {
+                self.complete_since = oldest;
+            }
+        }
+
+        self.cells.push_back((self.generation, coordinates));
+    }
+
+    // for writes too many to log one by one, every consumer refreshes everything
+    pub fn invalidate(&mut self) {
+        self.generation += 1;
+        self.complete_since = self.generation;
+        self.cells.clear();
+    }
+
+    // None when the log no longer reaches back to the given generation,
+    // in which case the consumer should treat everything as changed
+    pub fn since(&self, generation: u64) -> Option<Vec<Coordinates>> {
+        if generation < self.complete_since {
+            return None;
+        }
+
+        Some(
+            self.cells
+                .iter()
+                .filter(|it| it.0 > generation)
+                .map(|it| it.1)
+                .collect(),
+        )
+    }
 }
 
 impl<T> Matrix<T>
@@ -16,44 +75,225 @@ where
     T: Clone,
 {
     pub fn new(rows: usize, cols: usize, default_value: T) -> Self {
-        let len = rows * cols;
-        let vec = vec![default_value; len];
+        let mut matrix = Self::unloaded(rows, cols, default_value);
+
+        for index in 0..matrix.chunks.len() {
+            matrix.chunks[index] = Some(vec![matrix.default.clone(); CHUNK_SIZE * CHUNK_SIZE]);
+        }
 
-        Self { vec, rows, cols }
+        matrix
+    }
+
+    // no cell takes memory until its chunk is loaded, until then it reads as the default
+    pub fn unloaded(rows: usize, cols: usize, default_value: T) -> Self {
+        let len = rows.div_ceil(CHUNK_SIZE) * cols.div_ceil(CHUNK_SIZE);
+
+        Self {
+            chunks: vec![None; len],
+            default: default_value,
+            rows,
+            cols,
+            changes: ChangeLog::default(),
+        }
+    }
+
+    // the cells come row by row
+    pub fn from_cells(
+        rows: usize,
+        cols: usize,
+        default_value: T,
+        cells: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let mut matrix = Self::new(rows, cols, default_value);
+
+        for (index, cell) in cells.into_iter().take(rows * cols).enumerate() {
+            matrix[(index / cols, index % cols)] = cell;
+        }
+
+        matrix
     }
 
     pub fn contains(&self, coordinates: &Coordinates) -> bool {
         self.rows > coordinates.0 && self.cols > coordinates.1
     }
+
+    // every cell row by row, the default for cells of unloaded chunks
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.rows).flat_map(move |row| (0..self.cols).map(move |col| &self[(row, col)]))
+    }
+
+    pub fn is_chunk_loaded(&self, chunk: &ChunkCoordinates) -> bool {
+        self.chunk_index(chunk)
+            .is_some_and(|it| self.chunks[it].is_some())
+    }
+
+    // a loaded chunk starts out with every cell at the default
+    pub fn load_chunk(&mut self, chunk: &ChunkCoordinates) {
+        if let Some(index) = self.chunk_index(chunk) {
+            if self.chunks[index].is_none() {
+                self.chunks[index] = Some(vec![self.default.clone(); CHUNK_SIZE * CHUNK_SIZE]);
+            }
+        }
+    }
+
+    // the cells are dropped without being logged, they read as the default again
+    pub fn unload_chunk(&mut self, chunk: &ChunkCoordinates) {
+        if let Some(index) = self.chunk_index(chunk) {
+            self.chunks[index] = None;
+        }
+    }
+
+    fn chunk_index(&self, chunk: &ChunkCoordinates) -> Option<usize> {
+        let cols = self.cols.div_ceil(CHUNK_SIZE);
+
+        (chunk.0 < self.rows.div_ceil(CHUNK_SIZE) && chunk.1 < cols)
+            .then_some(chunk.0 * cols + chunk.1)
+    }
+
+    fn locate(&self, coordinates: Coordinates) -> (usize, usize) {
+        let (row, col) = (coordinates.row(), coordinates.col());
+        assert!(row < self.rows);
+        assert!(col < self.cols);
+
+        (
+            row / CHUNK_SIZE * self.cols.div_ceil(CHUNK_SIZE) + col / CHUNK_SIZE,
+            row % CHUNK_SIZE * CHUNK_SIZE + col % CHUNK_SIZE,
+        )
+    }
 }
 
-impl<T> IntoIterator for Matrix<T> {
+impl<T> Matrix<T>
+where
+    T: Clone + PartialEq,
+{
+    // writes that go through here are logged, but only when the value actually changes,
+    // cells of unloaded chunks are not there to change
+    pub fn set(&mut self, coordinates: Coordinates, value: T) -> bool {
+        let (chunk, _) = self.locate(coordinates);
+
+        if self.chunks[chunk].is_none() || self[coordinates] == value {
+            return false;
+        }
+
+        self[coordinates] = value;
+        self.changes.record(coordinates);
+
+        true
+    }
+}
+
+impl<T> IntoIterator for Matrix<T>
+where
+    T: Clone,
+{
     type Item = T;
     type IntoIter = std::vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.vec.into_iter()
+        self.iter().cloned().collect::<Vec<_>>().into_iter()
     }
 }
 
-impl<T> Index<Coordinates> for Matrix<T> {
+impl<T> Index<Coordinates> for Matrix<T>
+where
+    T: Clone,
+{
     type Output = T;
 
     fn index(&self, index: Coordinates) -> &Self::Output {
-        let (row, col) = (index.row(), index.col());
-        assert!(row < self.rows);
-        assert!(col < self.cols);
+        let (chunk, cell) = self.locate(index);
 
-        &self.vec[row * self.cols + col]
+        match &self.chunks[chunk] {
+            Some(cells) => &cells[cell],
+            None => &self.default,
+        }
     }
 }
 
-impl<T> IndexMut<Coordinates> for Matrix<T> {
+// writing to a cell of an unloaded chunk loads the chunk
+impl<T> IndexMut<Coordinates> for Matrix<T>
+where
+    T: Clone,
+{
     fn index_mut(&mut self, index: Coordinates) -> &mut Self::Output {
-        let (row, col) = (index.row(), index.col());
-        assert!(row < self.rows);
-        assert!(col < self.cols);
+        let (chunk, cell) = self.locate(index);
+        let default = &self.default;
+
+        &mut self.chunks[chunk]
+            .get_or_insert_with(|| vec![default.clone(); CHUNK_SIZE * CHUNK_SIZE])[cell]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logs_only_writes_that_change_a_cell() {
+        let mut matrix = Matrix::new(4, 4, 0);
+
+        assert!(matrix.set((1, 2), 5));
+        assert!(!matrix.set((1, 2), 5));
+        assert!(matrix.set((3, 0), 1));
+
+        assert_eq!(matrix.changes.generation(), 2);
+        assert_eq!(matrix.changes.since(0), Some(vec![(1, 2), (3, 0)]));
+        assert_eq!(matrix.changes.since(1), Some(vec![(3, 0)]));
+        assert_eq!(matrix.changes.since(2), Some(vec![]));
+    }
+
+    #[test]
+    fn since_gives_up_once_the_log_overflowed() {
+        let mut matrix = Matrix::new(CHUNK_SIZE, CHUNK_SIZE, 0);
+
+        for value in 1..=CHANGE_LOG_CAPACITY + 10 {
+            matrix.set((0, 0), value);
+        }
+
+        let generation = matrix.changes.generation();
+
+        assert_eq!(matrix.changes.since(0), None);
+        assert_eq!(matrix.changes.since(9), None);
+        assert_eq!(
+            matrix.changes.since(10).map(|it| it.len()),
+            Some(CHANGE_LOG_CAPACITY)
+        );
+        assert_eq!(matrix.changes.since(generation - 1), Some(vec![(0, 0)]));
+    }
+
+    #[test]
+    fn invalidate_makes_every_consumer_refresh() {
+        let mut matrix = Matrix::new(2, 2, false);
+
+        matrix.set((0, 1), true);
+
+        let generation = matrix.changes.generation();
+
+        matrix.changes.invalidate();
+
+        assert!(matrix.changes.generation() > generation);
+        assert_eq!(matrix.changes.since(generation), None);
+        assert_eq!(
+            matrix.changes.since(matrix.changes.generation()),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn cells_of_unloaded_chunks_read_as_the_default() {
+        let mut matrix = Matrix::unloaded(CHUNK_SIZE * 2, CHUNK_SIZE, 7);
+
+        assert!(!matrix.set((0, 0), 1));
+        assert_eq!(matrix[(0, 0)], 7);
+        assert_eq!(matrix.changes.generation(), 0);
+
+        matrix.load_chunk(&(1, 0));
+
+        assert!(matrix.set((CHUNK_SIZE, 0), 1));
+        assert!(!matrix.is_chunk_loaded(&(0, 0)));
+
+        matrix.unload_chunk(&(1, 0));
 
-        &mut self.vec[row * self.cols + col]
+        assert_eq!(matrix[(CHUNK_SIZE, 0)], 7);
     }
 }
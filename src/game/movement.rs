@@ -1,7 +1,19 @@
+use std::ops::Index;
+
 use super::coordinates::{Coordinates, CreateCoordinates};
 use super::matrix::Matrix;
 use super::node::Node;
 
+pub trait NodeGrid: Index<Coordinates, Output = Node> {
+    fn rows(&self) -> usize;
+    fn cols(&self) -> usize;
+
+    // weight of entering the cell for pathfinding
+    fn step_cost(&self, _index: &Coordinates) -> i32 {
+        1
+    }
+}
+
 pub trait Movement {
     fn nearest_neighbours(&self, index: &Coordinates) -> Vec<Option<Coordinates>>;
     fn left(&self, index: &Coordinates) -> Option<Coordinates>;
@@ -10,7 +22,20 @@ pub trait Movement {
     fn down(&self, index: &Coordinates) -> Option<Coordinates>;
 }
 
-impl Movement for Matrix<Node> {
+impl NodeGrid for Matrix<Node> {
+    fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn cols(&self) -> usize {
+        self.cols
+    }
+}
+
+impl<T> Movement for T
+where
+    T: NodeGrid,
+{
     #[inline(always)]
     fn nearest_neighbours(&self, index: &Coordinates) -> Vec<Option<Coordinates>> {
         Vec::from([
@@ -47,7 +72,7 @@ impl Movement for Matrix<Node> {
 
     #[inline(always)]
     fn right(&self, index: &Coordinates) -> Option<Coordinates> {
-        if index.col() < self.cols - 1 {
+        if index.col() < self.cols() - 1 {
             let index = (index.row(), index.col() + 1);
             let node = &self[index];
 
@@ -59,7 +84,7 @@ impl Movement for Matrix<Node> {
 
     #[inline(always)]
     fn down(&self, index: &Coordinates) -> Option<Coordinates> {
-        if index.row() < self.rows - 1 {
+        if index.row() < self.rows() - 1 {
             let index = (index.row() + 1, index.col());
             let node = &self[index];
 
@@ -2,6 +2,7 @@ use std::ops::{Index, IndexMut};
 
 use bevy::prelude::Component;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Entry {
     LEFT,
     TOP,
@@ -35,6 +36,22 @@ impl Node {
             bottom: false,
         }
     }
+
+    // doors and gates close single edges, only a cell without any open edge is a wall
+    pub fn is_wall(&self) -> bool {
+        !(self.left || self.top || self.right || self.bottom)
+    }
+}
+
+impl Entry {
+    pub fn opposite(&self) -> Entry {
+        match self {
+            Entry::LEFT => Entry::RIGHT,
+            Entry::TOP => Entry::BOTTOM,
+            Entry::RIGHT => Entry::LEFT,
+            Entry::BOTTOM => Entry::TOP,
+        }
+    }
 }
 
 impl From<u8> for Node {
@@ -0,0 +1,240 @@
+use std::ops::{Deref, DerefMut, Index};
+
+use bevy::prelude::Resource;
+
+use super::coordinates::{Coordinates, CreateCoordinates};
+use super::matrix::Matrix;
+use super::movement::NodeGrid;
+use super::node::Node;
+
+// every possible 4 bit node, so that Index can hand out references
+pub(crate) static NODES: [Node; 16] = {
+    let mut nodes = [Node {
+        left: false,
+        top: false,
+        right: false,
+        bottom: false,
+    }; 16];
+    let mut value = 0;
+
+    while value < 16 {
+        nodes[value] = Node {
+            left: value & 0b1000 == 0b1000,
+            top: value & 0b100 == 0b100,
+            right: value & 0b10 == 0b10,
+            bottom: value & 0b1 == 0b1,
+        };
+        value += 1;
+    }
+
+    nodes
+};
+
+// two nodes per byte, the even cell in the low nibble and the odd cell in the high nibble
+#[derive(Resource, Debug, Clone)]
+pub struct PackedMatrix {
+    pub bytes: Vec<u8>,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+pub struct NodeRef<'a> {
+    matrix: &'a mut PackedMatrix,
+    index: Coordinates,
+    node: Node,
+}
+
+impl PackedMatrix {
+    pub fn new(rows: usize, cols: usize, default_value: Node) -> Self {
+        let value: u8 = default_value.into();
+        let bytes = vec![value | (value << 4); (rows * cols).div_ceil(2)];
+
+        Self { bytes, rows, cols }
+    }
+
+    pub fn contains(&self, coordinates: &Coordinates) -> bool {
+        self.rows > coordinates.0 && self.cols > coordinates.1
+    }
+
+    pub fn get(&self, index: Coordinates) -> Node {
+        NODES[self.nibble(index) as usize]
+    }
+
+    pub fn set(&mut self, index: Coordinates, node: Node) {
+        let offset = self.offset(index);
+        let value: u8 = node.into();
+        let byte = &mut self.bytes[offset / 2];
+
+        *byte = match offset % 2 {
+            0 => (*byte & 0b11110000) | value,
+            _ => (*byte & 0b00001111) | (value << 4),
+        };
+    }
+
+    pub fn node_mut(&mut self, index: Coordinates) -> NodeRef<'_> {
+        let node = self.get(index);
+
+        NodeRef {
+            matrix: self,
+            index,
+            node,
+        }
+    }
+
+    #[inline(always)]
+    fn offset(&self, index: Coordinates) -> usize {
+        let (row, col) = (index.row(), index.col());
+        assert!(row < self.rows);
+        assert!(col < self.cols);
+
+        row * self.cols + col
+    }
+
+    #[inline(always)]
+    fn nibble(&self, index: Coordinates) -> u8 {
+        let offset = self.offset(index);
+        let byte = self.bytes[offset / 2];
+
+        match offset % 2 {
+            0 => byte & 0b1111,
+            _ => byte >> 4,
+        }
+    }
+}
+
+impl Index<Coordinates> for PackedMatrix {
+    type Output = Node;
+
+    fn index(&self, index: Coordinates) -> &Self::Output {
+        &NODES[self.nibble(index) as usize]
+    }
+}
+
+impl NodeGrid for PackedMatrix {
+    fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn cols(&self) -> usize {
+        self.cols
+    }
+}
+
+impl Deref for NodeRef<'_> {
+    type Target = Node;
+
+    fn deref(&self) -> &Self::Target {
+        &self.node
+    }
+}
+
+impl DerefMut for NodeRef<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.node
+    }
+}
+
+impl Drop for NodeRef<'_> {
+    fn drop(&mut self) {
+        self.matrix.set(self.index, self.node);
+    }
+}
+
+impl From<&Matrix<Node>> for PackedMatrix {
+    fn from(matrix: &Matrix<Node>) -> Self {
+        let mut packed = PackedMatrix::new(matrix.rows, matrix.cols, Node::closed());
+
+        matrix.iter().enumerate().for_each(|(index, node)| {
+            packed.set((index / matrix.cols, index % matrix.cols), *node);
+        });
+
+        packed
+    }
+}
+
+impl From<&PackedMatrix> for Matrix<Node> {
+    fn from(packed: &PackedMatrix) -> Self {
+        Matrix::from_cells(
+            packed.rows,
+            packed.cols,
+            Node::closed(),
+            (0..packed.rows * packed.cols)
+                .map(|index| packed.get((index / packed.cols, index % packed.cols))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::game::{
+        astar::{manhattan_heuristic, AStar},
+        node::Entry,
+    };
+
+    #[test]
+    fn every_node_fits_next_to_its_neighbour() {
+        let mut packed = PackedMatrix::new(3, 3, Node::open());
+
+        for (value, node) in NODES.iter().enumerate() {
+            packed.set((1, 0), *node);
+            packed.set((1, 1), NODES[15 - value]);
+
+            assert_eq!(packed.get((1, 0)), *node);
+            assert_eq!(packed[(1, 1)], NODES[15 - value]);
+            assert_eq!(packed[(0, 2)], Node::open());
+            assert_eq!(packed[(1, 2)], Node::open());
+        }
+    }
+
+    #[test]
+    fn node_ref_writes_back_when_dropped() {
+        let mut packed = PackedMatrix::new(1, 3, Node::closed());
+
+        {
+            let mut node = packed.node_mut((0, 1));
+
+            node[Entry::TOP] = true;
+            node[Entry::RIGHT] = true;
+        }
+
+        let node = packed.get((0, 1));
+
+        assert!(node.top && node.right && !node.left && !node.bottom);
+        assert!(packed.get((0, 0)).is_wall());
+        assert!(packed.get((0, 2)).is_wall());
+    }
+
+    #[test]
+    fn converts_to_and_from_a_matrix() {
+        let mut matrix = Matrix::new(5, 7, Node::open());
+
+        matrix[(0, 0)] = Node::closed();
+        matrix[(4, 6)].left = false;
+        matrix[(2, 3)] = NODES[0b1010];
+
+        let packed = PackedMatrix::from(&matrix);
+
+        assert!(matrix.iter().eq(Matrix::from(&packed).iter()));
+    }
+
+    #[test]
+    fn finds_the_same_path_as_the_matrix() {
+        let mut matrix = Matrix::new(12, 12, Node::open());
+
+        for row in 0..10 {
+            matrix[(row, 5)] = Node::closed();
+        }
+
+        let packed = PackedMatrix::from(&matrix);
+        let path = matrix.astar((0, 0), (0, 11), &manhattan_heuristic, &HashMap::new());
+
+        assert!(path.is_some());
+        assert_eq!(
+            packed.astar((0, 0), (0, 11), &manhattan_heuristic, &HashMap::new()),
+            path
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use bevy::prelude::Resource;
+use rand::prelude::*;
+
+// fixed offsets so that every stream derives its own sequence from the one seed
+const MAP_STREAM: u64 = 0x6d61_7000;
+const SPAWN_STREAM: u64 = 0x7370_6177;
+const LOOT_STREAM: u64 = 0x6c6f_6f74;
+const BEHAVIOUR_STREAM: u64 = 0x6265_6876;
+
+#[derive(Resource)]
+pub struct GameRng {
+    pub seed: u64,
+    pub map: StdRng,
+    pub spawns: StdRng,
+    pub loot: StdRng,
+    // enemy decisions draw from here, so they never shift where later floors spawn things
+    pub behaviour: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            map: StdRng::seed_from_u64(seed ^ MAP_STREAM),
+            spawns: StdRng::seed_from_u64(seed ^ SPAWN_STREAM),
+            loot: StdRng::seed_from_u64(seed ^ LOOT_STREAM),
+            behaviour: StdRng::seed_from_u64(seed ^ BEHAVIOUR_STREAM),
+        }
+    }
+
+    // reads `--seed <value>` from the command line, a random seed otherwise
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let seed = args
+            .iter()
+            .position(|it| it == "--seed")
+            .and_then(|index| args.get(index + 1))
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| rand::thread_rng().gen());
+
+        Self::new(seed)
+    }
+}
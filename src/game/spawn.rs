@@ -0,0 +1,163 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::{
+    coordinates::Coordinates,
+    door::Doors,
+    hazard::Hazard,
+    level::{Level, PlacementKind},
+    matrix::Matrix,
+    movement::{Movement, NodeGrid},
+    node::Node,
+};
+
+// cells around an enemy spawn placement that belong to its zone
+pub const SPAWN_ZONE_RADIUS: usize = 5;
+
+// steps from the start to every cell the grid lets an agent walk to, None for the others
+pub fn path_distances<T: NodeGrid>(grid: &T, start: Coordinates) -> Matrix<Option<u32>> {
+    let mut distances = Matrix::new(grid.rows(), grid.cols(), None);
+
+    if !distances.contains(&start) {
+        return distances;
+    }
+
+    let mut queue = VecDeque::from([start]);
+
+    distances[start] = Some(0);
+
+    while let Some(current) = queue.pop_front() {
+        let distance = distances[current].unwrap_or_default();
+
+        for next in grid.nearest_neighbours(&current).into_iter().flatten() {
+            if distances[next].is_none() {
+                distances[next] = Some(distance + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    distances
+}
+
+// the same steps, but only for cells at most max_distance steps away, in the order the search
+// reaches them, so that the search stays around the start on a large floor
+pub fn path_distances_within<T: NodeGrid>(
+    grid: &T,
+    start: Coordinates,
+    max_distance: u32,
+) -> Vec<(Coordinates, u32)> {
+    if start.0 >= grid.rows() || start.1 >= grid.cols() {
+        return Vec::new();
+    }
+
+    let mut seen = HashSet::from([start]);
+    let mut distances = vec![(start, 0)];
+    let mut next = 0;
+
+    while let Some(&(current, distance)) = distances.get(next) {
+        next += 1;
+
+        if distance == max_distance {
+            continue;
+        }
+
+        for neighbour in grid.nearest_neighbours(&current).into_iter().flatten() {
+            if seen.insert(neighbour) {
+                distances.push((neighbour, distance + 1));
+            }
+        }
+    }
+
+    distances
+}
+
+// enemies only appear on open ground, never on a hazard or where a closed door or gate is
+pub fn can_spawn_at(
+    matrix: &Matrix<Node>,
+    level: &Level,
+    doors: &Doors,
+    coordinates: &Coordinates,
+) -> bool {
+    matrix.contains(coordinates)
+        && !matrix[*coordinates].is_wall()
+        && level.hazard[*coordinates] == Hazard::None
+        && doors
+            .at(coordinates)
+            .iter()
+            .all(|(edge, _)| matrix[*coordinates][*edge])
+}
+
+// the enemy spawns that can matter for cells at most max_distance steps from the player,
+// when there are none enemies appear anywhere
+pub fn nearby_spawn_zones(
+    level: &Level,
+    player: &Coordinates,
+    max_distance: usize,
+) -> Vec<Coordinates> {
+    level
+        .placements_of(PlacementKind::EnemySpawn)
+        .filter(|it| {
+            it.0.abs_diff(player.0) + it.1.abs_diff(player.1) <= max_distance + SPAWN_ZONE_RADIUS
+        })
+        .collect()
+}
+
+pub fn in_spawn_zone(zones: &[Coordinates], coordinates: &Coordinates) -> bool {
+    zones.is_empty()
+        || zones.iter().any(|it| {
+            it.0.abs_diff(coordinates.0) + it.1.abs_diff(coordinates.1) <= SPAWN_ZONE_RADIUS
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::node::Entry;
+
+    #[test]
+    fn enemies_only_appear_on_safe_open_ground() {
+        let mut matrix = Matrix::new(3, 3, Node::open());
+        let mut level = Level::new(3, 3);
+
+        matrix[(0, 0)] = Node::closed();
+        level.hazard[(0, 1)] = Hazard::Mud;
+        matrix[(2, 2)].left = false;
+        matrix[(2, 1)].right = false;
+        level.place(
+            PlacementKind::Gate {
+                edge: Entry::LEFT,
+                channel: 0,
+            },
+            (2, 2),
+        );
+
+        let doors = Doors::from_level(&level, &matrix);
+        let spawnable = |it| can_spawn_at(&matrix, &level, &doors, &it);
+
+        assert!(!spawnable((0, 0)));
+        assert!(!spawnable((0, 1)));
+        assert!(!spawnable((2, 1)) && !spawnable((2, 2)));
+        assert!(!spawnable((3, 0)));
+        assert!(spawnable((1, 1)));
+
+        matrix[(2, 2)].left = true;
+        matrix[(2, 1)].right = true;
+
+        assert!(can_spawn_at(&matrix, &level, &doors, &(2, 2)));
+    }
+
+    #[test]
+    fn only_spawn_zones_near_the_player_hold_enemies_in() {
+        let mut level = Level::new(60, 60);
+
+        level.place(PlacementKind::EnemySpawn, (50, 50));
+
+        let far = nearby_spawn_zones(&level, &(0, 0), 10);
+        let near = nearby_spawn_zones(&level, &(45, 45), 10);
+
+        assert!(far.is_empty() && in_spawn_zone(&far, &(5, 5)));
+        assert_eq!(near, vec![(50, 50)]);
+        assert!(in_spawn_zone(&near, &(48, 47)));
+        assert!(!in_spawn_zone(&near, &(40, 40)));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use super::{
+    coordinates::Coordinates,
+    matrix::Matrix,
+    movement::{Movement, NodeGrid},
+    node::Node,
+};
+
+// neighbours closer than this many cells push each other apart
+pub const SEPARATION_RADIUS: f32 = 0.7;
+// how far in cells a sprite may stray from its path
+pub const MAX_OFFSET: f32 = 0.35;
+
+// points are the top left corners of sprites a cell in size, bucketed by the cell the sprite
+// covers the most of, so that only the buckets around a point are searched
+#[derive(Debug, Default)]
+pub struct SpatialHash {
+    buckets: HashMap<(i32, i32), Bucket>,
+}
+
+type Bucket = Vec<(usize, (f32, f32))>;
+
+impl SpatialHash {
+    fn bucket(point: (f32, f32)) -> (i32, i32) {
+        (point.0.round() as i32, point.1.round() as i32)
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+    }
+
+    pub fn insert(&mut self, id: usize, point: (f32, f32)) {
+        self.buckets
+            .entry(Self::bucket(point))
+            .or_default()
+            .push((id, point));
+    }
+
+    // every point within the radius but the one with the id
+    pub fn near(
+        &self,
+        id: usize,
+        point: (f32, f32),
+        radius: f32,
+    ) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let (row, col) = Self::bucket(point);
+        let reach = radius.ceil() as i32;
+
+        (row - reach..=row + reach)
+            .flat_map(move |r| (col - reach..=col + reach).map(move |c| (r, c)))
+            .filter_map(|it| self.buckets.get(&it))
+            .flatten()
+            .filter(move |(other, at)| {
+                *other != id && (at.0 - point.0).hypot(at.1 - point.1) < radius
+            })
+            .map(|(_, at)| *at)
+    }
+
+    // cells with a point in them
+    pub fn occupied(&self, cell: &Coordinates) -> bool {
+        self.buckets.contains_key(&(cell.0 as i32, cell.1 as i32))
+    }
+}
+
+// the sum of the pushes away from every neighbour, stronger the closer it is, neighbours right on
+// top of the point push along the fallback angle so that stacked sprites still come apart
+pub fn separation(
+    point: (f32, f32),
+    neighbours: impl Iterator<Item = (f32, f32)>,
+    fallback: f32,
+) -> (f32, f32) {
+    neighbours.fold((0., 0.), |push, at| {
+        let (row, col) = (point.0 - at.0, point.1 - at.1);
+        let distance = row.hypot(col);
+        let strength = (SEPARATION_RADIUS - distance) / SEPARATION_RADIUS;
+        let (row, col) = match distance > f32::EPSILON {
+            true => (row / distance, col / distance),
+            false => (fallback.sin(), fallback.cos()),
+        };
+
+        (push.0 + row * strength, push.1 + col * strength)
+    })
+}
+
+// cells with at most two ways out, where enemies wait in line instead of squeezing past
+pub fn is_corridor(matrix: &Matrix<Node>, cell: &Coordinates) -> bool {
+    matrix.contains(cell)
+        && matrix
+            .nearest_neighbours(cell)
+            .into_iter()
+            .flatten()
+            .count()
+            <= 2
+}
+
+// shortens the offset of a sprite a cell in size at the position so that it does not overlap a
+// wall it was not already overlapping, rows first, then columns next to the rows it ends up in
+pub fn clamp_off_walls<T: NodeGrid>(
+    grid: &T,
+    position: (f32, f32),
+    offset: (f32, f32),
+) -> (f32, f32) {
+    let wall = |row: i32, col: i32| {
+        row < 0
+            || col < 0
+            || row as usize >= grid.rows()
+            || col as usize >= grid.cols()
+            || grid[(row as usize, col as usize)].is_wall()
+    };
+    let span = |from: f32| (from.floor() as i32, from.ceil() as i32);
+    let clamp = |from: f32, by: f32, blocked: &dyn Fn(i32) -> bool| {
+        let (low, high) = span(from);
+
+        match by {
+            _ if by > 0. && (from + by).ceil() as i32 > high && blocked(high + 1) => {
+                by.min(high as f32 - from)
+            }
+            _ if by < 0. && ((from + by).floor() as i32) < low && blocked(low - 1) => {
+                by.max(low as f32 - from)
+            }
+            _ => by,
+        }
+    };
+    let (left, right) = span(position.1);
+    let row = clamp(position.0, offset.0, &|row| {
+        wall(row, left) || wall(row, right)
+    });
+    let (top, bottom) = span(position.0 + row);
+    let col = clamp(position.1, offset.1, &|col| {
+        wall(top, col) || wall(bottom, col)
+    });
+
+    (row, col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn near_skips_the_point_itself_and_far_points() {
+        let mut hash = SpatialHash::default();
+
+        hash.insert(0, (0., 0.));
+        hash.insert(1, (0.5, 0.));
+        hash.insert(2, (3., 3.));
+
+        assert_eq!(hash.near(0, (0., 0.), 0.7).collect::<Vec<_>>(), [(0.5, 0.)]);
+        assert!(hash.occupied(&(3, 3)));
+        assert!(!hash.occupied(&(1, 1)));
+    }
+
+    #[test]
+    fn separation_pushes_away_and_apart_when_stacked() {
+        let push = separation((0., 0.), [(0., 0.35)].into_iter(), 0.);
+
+        assert_eq!(push.0, 0.);
+        assert!(push.1 < 0.);
+        assert_eq!(separation((0., 0.), [(0., 0.)].into_iter(), 0.), (0., 1.));
+    }
+
+    #[test]
+    fn corridors_have_at_most_two_ways_out() {
+        let mut matrix = Matrix::new(3, 3, Node::open());
+
+        assert!(!is_corridor(&matrix, &(1, 1)));
+
+        matrix[(0, 1)] = Node::closed();
+        matrix[(2, 1)] = Node::closed();
+
+        assert!(is_corridor(&matrix, &(1, 1)));
+    }
+
+    #[test]
+    fn clamp_off_walls_stops_at_new_walls_only() {
+        let mut matrix = Matrix::new(3, 3, Node::open());
+
+        matrix[(1, 2)] = Node::closed();
+
+        assert_eq!(clamp_off_walls(&matrix, (1., 1.), (0., 0.3)), (0., 0.));
+        assert_eq!(clamp_off_walls(&matrix, (1., 1.), (0., -0.3)), (0., -0.3));
+        assert_eq!(clamp_off_walls(&matrix, (1., 1.), (0.2, 0.)), (0.2, 0.));
+        // the border of the grid counts as wall
+        assert_eq!(clamp_off_walls(&matrix, (0., 0.), (-0.3, -0.3)), (0., 0.));
+        // a sprite already overlapping the wall may move further into it
+        assert_eq!(clamp_off_walls(&matrix, (1., 1.5), (0., 0.2)), (0., 0.2));
+    }
+}
@@ -0,0 +1,106 @@
+use bevy::prelude::{warn, Resource};
+use serde::Deserialize;
+
+// a wave spreads its count over its time in seconds, picking archetypes by name from the
+// weighted mix and placing them (min, max) steps of walking away from the player, the breather
+// is the rest in seconds before the next wave
+#[derive(Deserialize, Debug, Clone)]
+pub struct Wave {
+    pub time: f32,
+    pub count: usize,
+    pub mix: Vec<(String, u32)>,
+    pub ring: (usize, usize),
+    #[serde(default)]
+    pub breather: f32,
+}
+
+// the table starts over after its last wave, counts grow by loop_ramp every time it does
+// and by floor_ramp for every floor down, no spawns while max_alive enemies are around
+#[derive(Resource, Deserialize, Debug, Clone)]
+pub struct WaveTable {
+    pub waves: Vec<Wave>,
+    pub loop_ramp: f32,
+    pub floor_ramp: f32,
+    pub max_alive: usize,
+}
+
+impl Default for WaveTable {
+    fn default() -> Self {
+        let wave = |time: f32, count: usize, ring: (usize, usize), breather: f32| Wave {
+            time,
+            count,
+            mix: Vec::new(),
+            ring,
+            breather,
+        };
+
+        Self {
+            waves: vec![
+                wave(20., 20, (10, 20), 8.),
+                wave(25., 40, (10, 20), 8.),
+                wave(30., 70, (8, 24), 12.),
+            ],
+            loop_ramp: 0.5,
+            floor_ramp: 0.3,
+            max_alive: 300,
+        }
+    }
+}
+
+impl WaveTable {
+    pub fn from_file(file_name: &str) -> Self {
+        std::fs::read_to_string(file_name)
+            .map_err(|error| error.to_string())
+            .and_then(|raw| ron::from_str(&raw).map_err(|error| error.to_string()))
+            .unwrap_or_else(|error| {
+                warn!(
+                    "could not load {}, using the default waves: {}",
+                    file_name, error
+                );
+
+                Self::default()
+            })
+    }
+
+    // the wave at the index counted from the start of the floor, with its ramped count
+    pub fn wave(&self, index: usize, floor: usize) -> Option<(&Wave, usize)> {
+        if self.waves.is_empty() {
+            return None;
+        }
+
+        let wave = &self.waves[index % self.waves.len()];
+        let loops = (index / self.waves.len()) as f32;
+        let depth = floor.saturating_sub(1) as f32;
+        let difficulty = (1. + self.loop_ramp * loops) * (1. + self.floor_ramp * depth);
+
+        Some((wave, (wave.count as f32 * difficulty).round() as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waves_loop_and_ramp_up() {
+        let table = WaveTable::default();
+
+        assert_eq!(table.wave(0, 1).map(|it| it.1), Some(20));
+        assert_eq!(table.wave(2, 1).map(|it| it.1), Some(70));
+        // the second time through the table
+        assert_eq!(table.wave(3, 1).map(|it| it.1), Some(30));
+        // a floor down
+        assert_eq!(table.wave(0, 2).map(|it| it.1), Some(26));
+        assert_eq!(table.wave(4, 2).map(|it| it.1), Some(78));
+    }
+
+    #[test]
+    fn an_empty_table_has_no_waves() {
+        let table = WaveTable {
+            waves: Vec::new(),
+            ..Default::default()
+        };
+
+        assert!(table.wave(0, 1).is_none());
+    }
+}
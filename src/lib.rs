@@ -1,7 +1,8 @@
 #![feature(binary_heap_retain)]
 
 use bevy::prelude::*;
-use game::{coordinates::Coordinates, node::Node};
+use game::{archetype::EnemyArchetype, coordinates::Coordinates, node::Node};
+use rand::prelude::*;
 
 pub mod game;
 pub mod plugin;
@@ -12,21 +13,47 @@ pub enum UserCursorPressedState {
     DOWN,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    Playing,
+    Editing,
+    GameOver,
+}
+
 #[derive(Resource)]
 pub struct GridSize(pub (usize, usize));
 
 #[derive(Resource)]
 pub struct NodeSize(pub (f32, f32));
 
+// the world is laid out around the player, whose cell has its top left corner at the origin,
+// cells are (row, col) and may be fractional while something moves between two cells
+impl NodeSize {
+    pub fn cell_corner(&self, live_position: &LivePosition, cell: (f32, f32)) -> Vec2 {
+        Vec2::new(
+            (cell.1 - live_position.0 .1) * self.0 .0,
+            (live_position.0 .0 - cell.0) * self.0 .1,
+        )
+    }
+
+    pub fn cell_center(&self, live_position: &LivePosition, cell: (f32, f32)) -> Vec2 {
+        self.cell_corner(live_position, cell) + Vec2::new(self.0 .0 / 2., -self.0 .1 / 2.)
+    }
+
+    pub fn world_to_cell(&self, live_position: &LivePosition, world: Vec2) -> (f32, f32) {
+        (
+            live_position.0 .0 - world.y / self.0 .1,
+            live_position.0 .1 + world.x / self.0 .0,
+        )
+    }
+}
+
 #[derive(Component, Debug, Clone, Copy)]
 pub struct Position(pub Coordinates);
 
-#[derive(Component, Debug, Clone, Copy)]
+#[derive(Component, Debug, Clone, Copy, Default)]
 pub struct LivePosition(pub (f32, f32));
 
-#[derive(Resource)]
-pub struct EnemyCount(pub i16);
-
 #[derive(Component, Debug)]
 struct Player {}
 
@@ -69,7 +96,7 @@ struct WalkAnimationTimer(Timer);
 
 #[derive(Component)]
 struct EnemyType {
-    type_value: EnemyTypeValue,
+    archetype: Handle<EnemyArchetype>,
 }
 
 #[derive(Component)]
@@ -78,170 +105,64 @@ pub struct Durability(pub u16);
 #[derive(Component)]
 pub struct Health(pub u16);
 
-#[derive(Resource)]
-pub struct EnemySprites {
-    pub size: f32,
-    pub bat_up: Handle<TextureAtlas>,
-    pub bat_down: Handle<TextureAtlas>,
-    pub bat_left: Handle<TextureAtlas>,
-    pub bat_right: Handle<TextureAtlas>,
-    pub spider_up: Handle<TextureAtlas>,
-    pub spider_down: Handle<TextureAtlas>,
-    pub spider_left: Handle<TextureAtlas>,
-    pub spider_right: Handle<TextureAtlas>,
-    pub skeleton_up: Handle<TextureAtlas>,
-    pub skeleton_down: Handle<TextureAtlas>,
-    pub skeleton_left: Handle<TextureAtlas>,
-    pub skeleton_right: Handle<TextureAtlas>,
+// projectiles only hurt the other side
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Enemy,
 }
 
-pub enum EnemyTypeValue {
-    Bat,
-    Spider,
-    Skeleton,
+// the folder handles keep the archetype files loaded and hot reloadable,
+// loaded mirrors the finished ones sorted by name, so that seeded picks do not depend on load order
+#[derive(Resource)]
+pub struct EnemyArchetypes {
+    pub folder: Vec<HandleUntyped>,
+    pub loaded: Vec<(Handle<EnemyArchetype>, EnemyArchetype)>,
 }
 
-impl EnemySprites {
-    pub fn init(
-        asset_server: &Res<AssetServer>,
-        texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
-    ) -> Self {
+impl EnemyArchetypes {
+    pub fn load(asset_server: &Res<AssetServer>) -> Self {
+        let folder = asset_server.load_folder("enemies").unwrap_or_else(|error| {
+            warn!("could not load the enemy archetypes: {}", error);
+
+            Vec::new()
+        });
+
         Self {
-            size: 32.,
-            bat_up: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("bat_up.png"),
-                Vec2::new(32., 32.),
-                3,
-                1,
-                None,
-                None,
-            )),
-            bat_down: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("bat_down.png"),
-                Vec2::new(32., 32.),
-                3,
-                1,
-                None,
-                None,
-            )),
-            bat_left: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("bat_left.png"),
-                Vec2::new(32., 32.),
-                3,
-                1,
-                None,
-                None,
-            )),
-            bat_right: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("bat_right.png"),
-                Vec2::new(32., 32.),
-                3,
-                1,
-                None,
-                None,
-            )),
-            spider_up: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("spider_up.png"),
-                Vec2::new(32., 32.),
-                6,
-                1,
-                None,
-                None,
-            )),
-            spider_down: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("spider_down.png"),
-                Vec2::new(32., 32.),
-                6,
-                1,
-                None,
-                None,
-            )),
-            spider_left: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("spider_left.png"),
-                Vec2::new(32., 32.),
-                6,
-                1,
-                None,
-                None,
-            )),
-            spider_right: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("spider_right.png"),
-                Vec2::new(32., 32.),
-                6,
-                1,
-                None,
-                None,
-            )),
-            skeleton_up: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("skeleton_up.png"),
-                Vec2::new(32., 32.),
-                8,
-                1,
-                None,
-                None,
-            )),
-            skeleton_down: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("skeleton_down.png"),
-                Vec2::new(32., 32.),
-                8,
-                1,
-                None,
-                None,
-            )),
-            skeleton_left: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("skeleton_left.png"),
-                Vec2::new(32., 32.),
-                8,
-                1,
-                None,
-                None,
-            )),
-            skeleton_right: texture_atlases.add(TextureAtlas::from_grid(
-                asset_server.load("skeleton_right.png"),
-                Vec2::new(32., 32.),
-                8,
-                1,
-                None,
-                None,
-            )),
+            folder,
+            loaded: Vec::new(),
         }
     }
 
-    pub fn find(
-        &self,
-        from: &Coordinates,
-        to: &Coordinates,
-        type_value: &EnemyTypeValue,
-    ) -> Option<Handle<TextureAtlas>> {
-        match type_value {
-            EnemyTypeValue::Bat => Some(if from.1 > to.1 {
-                self.bat_left.clone()
-            } else if from.1 < to.1 {
-                self.bat_right.clone()
-            } else if from.0 > to.0 {
-                self.bat_up.clone()
-            } else {
-                self.bat_down.clone()
-            }),
-            EnemyTypeValue::Spider => Some(if from.1 > to.1 {
-                self.spider_left.clone()
-            } else if from.1 < to.1 {
-                self.spider_right.clone()
-            } else if from.0 > to.0 {
-                self.spider_up.clone()
-            } else {
-                self.spider_down.clone()
-            }),
-            EnemyTypeValue::Skeleton => Some(if from.1 > to.1 {
-                self.skeleton_left.clone()
-            } else if from.1 < to.1 {
-                self.skeleton_right.clone()
-            } else if from.0 > to.0 {
-                self.skeleton_up.clone()
-            } else {
-                self.skeleton_down.clone()
-            }),
+    pub fn get(&self, handle: &Handle<EnemyArchetype>) -> Option<&EnemyArchetype> {
+        self.loaded
+            .iter()
+            .find(|it| it.0 == *handle)
+            .map(|it| &it.1)
+    }
+
+    pub fn named(&self, name: &str) -> Option<&(Handle<EnemyArchetype>, EnemyArchetype)> {
+        self.loaded.iter().find(|it| it.1.name == name)
+    }
+
+    // weighted by the spawn weight of every archetype
+    pub fn pick(&self, rng: &mut StdRng) -> Option<&(Handle<EnemyArchetype>, EnemyArchetype)> {
+        let total: u32 = self.loaded.iter().map(|it| it.1.weight).sum();
+
+        if total == 0 {
+            return None;
         }
+
+        let mut roll = rng.gen_range(0..total);
+
+        self.loaded.iter().find(|it| match roll < it.1.weight {
+            true => true,
+            false => {
+                roll -= it.1.weight;
+
+                false
+            }
+        })
     }
 }
 
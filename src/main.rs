@@ -6,13 +6,19 @@ use bevy::{
 };
 
 use letterbox::{
-    game::coordinates::Coordinates,
+    game::{
+        autotile::WallTileset, coordinates::Coordinates, map_gen::MapGenConfig, rng::GameRng,
+        wave::WaveTable,
+    },
     plugin::{
-        assets::AssetsPlugin, enemy::EnemyPlugin, grid::GridPlugin, player::PlayerPlugin,
-        power_up::PowerUpPlugin,
+        assets::AssetsPlugin, boss::BossPlugin, camera::CameraPlugin, door::DoorPlugin,
+        editor::EditorPlugin, enemy::EnemyPlugin, floor::FloorPlugin, game_over::GameOverPlugin,
+        grid::GridPlugin, grid_mesh::GridMeshPlugin, grid_sprite::GridSpritePlugin,
+        hazard::HazardPlugin, hud::HudPlugin, minimap::MinimapPlugin, player::PlayerPlugin,
+        power_up::PowerUpPlugin, spawn::SpawnPlugin, wall::WallPlugin, wave::WavePlugin,
     },
-    AttackSprites, EnemyCount, EnemySprites, FragSprites, GridSize, NodeSize, PlayerSprites,
-    PowerUpSprites, ProjectileReach, ProjectileSprites,
+    AttackSprites, EnemyArchetypes, FragSprites, GridSize, NodeSize, PlayerSprites, PowerUpSprites,
+    ProjectileReach, ProjectileSprites,
 };
 
 // (rows, cols)
@@ -21,11 +27,15 @@ const GRID_SIZE: (usize, usize) = (120, 120);
 const NODE_SIZE: (f32, f32) = (48., 48.);
 
 fn main() {
-    App::new()
-        .insert_resource(GridSize(GRID_SIZE))
+    let mut app = App::new();
+
+    app.insert_resource(GridSize(GRID_SIZE))
         .insert_resource(NodeSize(NODE_SIZE))
-        .insert_resource(EnemyCount(1000))
         .insert_resource(ProjectileReach(5))
+        .insert_resource(MapGenConfig::from_file("assets/mapgen.ron"))
+        .insert_resource(WaveTable::from_file("assets/waves.ron"))
+        .insert_resource(WallTileset::from_file("assets/tileset.ron"))
+        .insert_resource(GameRng::from_args())
         .add_startup_system(setup_system)
         .add_plugins(
             DefaultPlugins
@@ -43,11 +53,39 @@ fn main() {
         .add_plugin(LogDiagnosticsPlugin::default())
         .add_plugin(FrameTimeDiagnosticsPlugin::default())
         .add_plugin(AssetsPlugin)
+        .add_plugin(CameraPlugin)
         .add_plugin(GridPlugin)
+        .add_plugin(FloorPlugin)
+        .add_plugin(WallPlugin)
+        .add_plugin(DoorPlugin)
+        .add_plugin(HazardPlugin)
         .add_plugin(PlayerPlugin)
         .add_plugin(EnemyPlugin)
+        .add_plugin(SpawnPlugin)
+        .add_plugin(BossPlugin)
+        .add_plugin(WavePlugin)
         .add_plugin(PowerUpPlugin)
-        .run();
+        .add_plugin(HudPlugin)
+        .add_plugin(GameOverPlugin)
+        .add_plugin(MinimapPlugin)
+        .add_plugin(EditorPlugin);
+
+    // `--renderer mesh` draws the grid as batched chunk meshes instead of sprites
+    match renderer_from_args().as_deref() {
+        Some("mesh") => app.add_plugin(GridMeshPlugin),
+        _ => app.add_plugin(GridSpritePlugin),
+    };
+
+    app.run();
+}
+
+fn renderer_from_args() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    args.iter()
+        .position(|it| it == "--renderer")
+        .and_then(|index| args.get(index + 1))
+        .cloned()
 }
 
 fn setup_system(
@@ -64,7 +102,7 @@ fn setup_system(
 
     commands.insert_resource(PlayerSprites::init(&asset_server, &mut texture_atlases));
     commands.insert_resource(AttackSprites::init(&asset_server, &mut texture_atlases));
-    commands.insert_resource(EnemySprites::init(&asset_server, &mut texture_atlases));
+    commands.insert_resource(EnemyArchetypes::load(&asset_server));
     commands.insert_resource(ProjectileSprites::init(&asset_server, &mut texture_atlases));
     commands.insert_resource(FragSprites::init(&asset_server, &mut texture_atlases));
     commands.insert_resource(PowerUpSprites::init(&asset_server, &mut texture_atlases));
@@ -1,6 +1,20 @@
 pub mod assets;
+pub mod boss;
+pub mod camera;
+pub mod door;
+pub mod editor;
 pub mod enemy;
+pub mod floor;
+pub mod game_over;
 pub mod grid;
+pub mod grid_mesh;
+pub mod grid_sprite;
+pub mod hazard;
+pub mod hud;
+pub mod minimap;
 pub mod player;
 pub mod power_up;
 pub mod projectile;
+pub mod spawn;
+pub mod wall;
+pub mod wave;
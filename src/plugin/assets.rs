@@ -1,6 +1,13 @@
 use bevy::prelude::*;
 
-use crate::{game::coordinates::Coordinates, Position};
+use crate::game::{
+    autotile::{wall_mask, WallTileset},
+    coordinates::Coordinates,
+    level::{Decoration, Level},
+    matrix::Matrix,
+    movement::Movement,
+    node::Node,
+};
 pub struct AssetsPlugin;
 
 #[derive(Resource)]
@@ -31,11 +38,14 @@ pub(crate) struct GridTextures {
     wall_tile_0111: Handle<Image>,
     wall_tile_1011: Handle<Image>,
     wall_tile_1101: Handle<Image>,
+    //
+    wall_tileset: WallTileset,
+    wall_atlas: Option<Handle<Image>>,
 }
 
 impl GridTextures {
-    pub(crate) fn random_floor_tile(&self, position: &Position) -> Handle<Image> {
-        match (position.0 .0 * position.0 .1) % 9 {
+    pub(crate) fn floor_tile(&self, variant: u8) -> Handle<Image> {
+        match variant {
             0 => self.floor_tile_0.clone(),
             1 => self.floor_tile_1.clone(),
             2 => self.floor_tile_2.clone(),
@@ -48,6 +58,81 @@ impl GridTextures {
         }
     }
 
+    pub(crate) fn handles(&self) -> Vec<Handle<Image>> {
+        vec![
+            self.floor_tile_0.clone(),
+            self.floor_tile_1.clone(),
+            self.floor_tile_2.clone(),
+            self.floor_tile_3.clone(),
+            self.floor_tile_4.clone(),
+            self.floor_tile_5.clone(),
+            self.floor_tile_6.clone(),
+            self.floor_tile_7.clone(),
+            self.floor_tile_8.clone(),
+            self.wall_tile_1111.clone(),
+            self.wall_tile_0000.clone(),
+            self.wall_tile_1000.clone(),
+            self.wall_tile_0100.clone(),
+            self.wall_tile_0010.clone(),
+            self.wall_tile_0001.clone(),
+            self.wall_tile_1100.clone(),
+            self.wall_tile_1010.clone(),
+            self.wall_tile_0110.clone(),
+            self.wall_tile_0101.clone(),
+            self.wall_tile_0011.clone(),
+            self.wall_tile_1001.clone(),
+            self.wall_tile_1110.clone(),
+            self.wall_tile_0111.clone(),
+            self.wall_tile_1011.clone(),
+            self.wall_tile_1101.clone(),
+        ]
+        .into_iter()
+        .chain(self.wall_atlas.clone())
+        .collect()
+    }
+
+    // texture, region within that texture and tint of a single cell, shared by the grid renderers
+    pub(crate) fn resolve_cell(
+        &self,
+        matrix: &Matrix<Node>,
+        level: &Level,
+        coordinates: &Coordinates,
+    ) -> (Handle<Image>, Option<Rect>, Color) {
+        match matrix[*coordinates].is_wall() {
+            false => (
+                self.floor_tile(level.floor[*coordinates]),
+                None,
+                decoration_tint(&level.decoration[*coordinates]),
+            ),
+            true => {
+                if let (Some(atlas), WallTileset::Blob { tile_size, .. }) =
+                    (&self.wall_atlas, &self.wall_tileset)
+                {
+                    if let Some((x, y)) =
+                        self.wall_tileset.blob_tile(wall_mask(matrix, coordinates))
+                    {
+                        return (
+                            atlas.clone(),
+                            Some(Rect::new(x, y, x + tile_size.0, y + tile_size.1)),
+                            Color::WHITE,
+                        );
+                    }
+                }
+
+                let left = matrix.left(coordinates);
+                let top = matrix.up(coordinates);
+                let right = matrix.right(coordinates);
+                let bottom = matrix.down(coordinates);
+
+                (
+                    self.resolve_wall_tile(&left, &top, &right, &bottom),
+                    None,
+                    Color::WHITE,
+                )
+            }
+        }
+    }
+
     pub(crate) fn resolve_wall_tile(
         &self,
         left: &Option<Coordinates>,
@@ -81,9 +166,19 @@ impl GridTextures {
     }
 }
 
+fn decoration_tint(decoration: &Decoration) -> Color {
+    match decoration {
+        Decoration::None => Color::WHITE,
+        Decoration::Moss => Color::rgb(0.7, 1.0, 0.7),
+        Decoration::Blood => Color::rgb(1.0, 0.6, 0.6),
+        Decoration::Rubble => Color::rgb(0.7, 0.7, 0.7),
+    }
+}
+
 impl Plugin for AssetsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup_system);
+        app.init_resource::<WallTileset>()
+            .add_startup_system(setup_system);
     }
 
     fn name(&self) -> &str {
@@ -91,7 +186,11 @@ impl Plugin for AssetsPlugin {
     }
 }
 
-fn setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    wall_tileset: Res<WallTileset>,
+) {
     commands.insert_resource(GridTextures {
         floor_tile_0: asset_server.load("floor_0.png"),
         floor_tile_1: asset_server.load("floor_1.png"),
@@ -119,5 +218,11 @@ fn setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
         wall_tile_1011: asset_server.load("wall_1011.png"),
         wall_tile_1101: asset_server.load("wall_1101.png"),
         wall_tile_1111: asset_server.load("wall_1111.png"),
+        //
+        wall_tileset: wall_tileset.clone(),
+        wall_atlas: match &*wall_tileset {
+            WallTileset::Legacy => None,
+            WallTileset::Blob { image, .. } => Some(asset_server.load(image.as_str())),
+        },
     });
 }
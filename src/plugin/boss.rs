@@ -0,0 +1,416 @@
+use std::{collections::HashSet, f32::consts::TAU, time::Duration};
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    game::{
+        archetype::BossPattern,
+        behaviour::BehaviourState,
+        coordinates::Coordinates,
+        dungeon::{Dungeon, EnemySettings},
+        matrix::Matrix,
+        node::Node,
+        rng::GameRng,
+    },
+    EnemyArchetypes, EnemyType, Faction, GameMode, Health, NodeSize, Position, ProjectileSprites,
+};
+
+use super::{
+    enemy::{reach, spawn_archetype, Behaviour, EnemyDied, WalkAnimationTimer},
+    floor::FloorChanged,
+    projectile::{spawn_projectile, Explosive},
+    spawn::SpawnPlacer,
+};
+
+// bosses appear this many steps of walking away from the player, never closer than the minimum
+pub(crate) const SPAWN_DISTANCE: (usize, usize) = (8, 20);
+
+// seconds spent on the current floor, and the bosses already beaten as (name, floor)
+#[derive(Resource, Default)]
+struct BossDirector {
+    floor_time: f32,
+    defeated: HashSet<(String, usize)>,
+}
+
+#[derive(Component)]
+struct Boss {
+    phase: Option<usize>,
+    pattern: usize,
+    cooldown: Timer,
+}
+
+// the step time to go back to once the charge is over
+#[derive(Component)]
+struct Charging {
+    timer: Timer,
+    step_time: Duration,
+}
+
+#[derive(Component)]
+struct BossBar;
+
+#[derive(Component)]
+struct BossName;
+
+#[derive(Component)]
+struct BossFill;
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BossDirector>()
+            .add_startup_system(setup_system)
+            .add_system(change_floor_system)
+            .add_system(defeat_system)
+            .add_system_set(
+                SystemSet::on_update(GameMode::Playing)
+                    .with_system(spawn_system)
+                    .with_system(phase_system)
+                    .with_system(charge_system),
+            )
+            .add_system(render_bar_system);
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("DejaVuSansMono.ttf");
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(8.),
+                        left: Val::Percent(25.),
+                        ..default()
+                    },
+                    size: Size::new(Val::Percent(50.), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                visibility: Visibility::INVISIBLE,
+                ..default()
+            },
+            BossBar,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font,
+                        font_size: 16.,
+                        color: Color::WHITE,
+                    },
+                ),
+                BossName,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Px(10.)),
+                        margin: UiRect::top(Val::Px(4.)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.2, 0.05, 0.05, 0.8).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                ..default()
+                            },
+                            background_color: Color::rgb(0.8, 0.1, 0.1).into(),
+                            ..default()
+                        },
+                        BossFill,
+                    ));
+                });
+        });
+}
+
+fn change_floor_system(
+    mut director: ResMut<BossDirector>,
+    mut floor_changed_events: EventReader<FloorChanged>,
+) {
+    if floor_changed_events.iter().last().is_some() {
+        director.floor_time = 0.;
+    }
+}
+
+fn defeat_system(
+    dungeon: Res<Dungeon>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    mut director: ResMut<BossDirector>,
+    mut died_events: EventReader<EnemyDied>,
+) {
+    for event in died_events.iter() {
+        if let Some(archetype) = enemy_archetypes.get(&event.archetype) {
+            if archetype.boss.is_some() {
+                director
+                    .defeated
+                    .insert((archetype.name.clone(), dungeon.floor));
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+// every boss of the floor that is neither beaten nor around shows up once its time has come
+fn spawn_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    dungeon: Res<Dungeon>,
+    matrix: Res<Matrix<Node>>,
+    node_size: Res<NodeSize>,
+    placer: Res<SpawnPlacer>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    enemy_settings: Res<EnemySettings>,
+    mut director: ResMut<BossDirector>,
+    mut game_rng: ResMut<GameRng>,
+    query: Query<&EnemyType, With<Boss>>,
+) {
+    director.floor_time += time.delta_seconds();
+
+    for (handle, archetype) in &enemy_archetypes.loaded {
+        let config = match &archetype.boss {
+            Some(config) => config,
+            None => continue,
+        };
+
+        if !config.floors.contains(&dungeon.floor)
+            || director.floor_time < config.after
+            || director
+                .defeated
+                .contains(&(archetype.name.clone(), dungeon.floor))
+            || query.iter().any(|it| it.archetype == *handle)
+        {
+            continue;
+        }
+
+        let fits = |anchor: &Coordinates| {
+            (anchor.0..anchor.0 + config.footprint)
+                .flat_map(|row| (anchor.1..anchor.1 + config.footprint).map(move |col| (row, col)))
+                .all(|it| matrix.contains(&it) && !matrix[it].is_wall())
+        };
+        let rng = &mut game_rng.spawns;
+        let start = placer.pick(rng, SPAWN_DISTANCE, fits);
+
+        if let Some(start) = start {
+            let entity = spawn_archetype(
+                &mut commands,
+                &node_size,
+                &enemy_settings,
+                (handle, archetype),
+                start,
+                start,
+                rng,
+            );
+
+            commands.entity(entity).insert(Boss {
+                phase: None,
+                pattern: 0,
+                cooldown: Timer::from_seconds(0., TimerMode::Repeating),
+            });
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+// the phase follows the health left, its patterns take turns while the boss is fighting
+fn phase_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    matrix: Res<Matrix<Node>>,
+    node_size: Res<NodeSize>,
+    projectile_sprites: Res<ProjectileSprites>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    enemy_settings: Res<EnemySettings>,
+    mut game_rng: ResMut<GameRng>,
+    mut query: Query<(
+        Entity,
+        &mut Boss,
+        &Health,
+        &Behaviour,
+        &Position,
+        &EnemyType,
+        &mut WalkAnimationTimer,
+        Option<&Charging>,
+    )>,
+) {
+    for (entity, mut boss, health, behaviour, position, enemy_type, mut walk, charging) in
+        &mut query
+    {
+        let (archetype, config) = match enemy_archetypes.get(&enemy_type.archetype) {
+            Some(archetype) => match &archetype.boss {
+                Some(config) => (archetype, config),
+                None => continue,
+            },
+            None => continue,
+        };
+        let share = health.0 as f32 / behaviour.max_health.max(1) as f32;
+        let phase = config
+            .phases
+            .iter()
+            .rposition(|it| share < it.below)
+            .unwrap_or(0);
+        let phase_config = match config.phases.get(phase) {
+            Some(phase_config) => phase_config,
+            None => continue,
+        };
+
+        if boss.phase != Some(phase) {
+            boss.phase = Some(phase);
+            boss.pattern = 0;
+            boss.cooldown = Timer::from_seconds(phase_config.cooldown, TimerMode::Repeating);
+        }
+
+        if !matches!(
+            behaviour.state,
+            BehaviourState::Chase | BehaviourState::Attack
+        ) {
+            continue;
+        }
+
+        boss.cooldown.tick(time.delta());
+
+        if !boss.cooldown.just_finished() || phase_config.patterns.is_empty() {
+            continue;
+        }
+
+        let pattern = phase_config.patterns[boss.pattern % phase_config.patterns.len()];
+        let footprint = archetype.footprint();
+
+        boss.pattern += 1;
+
+        match pattern {
+            BossPattern::Ring {
+                count,
+                damage,
+                blast,
+            } => {
+                let offset = (footprint - 1) as f32 / 2.;
+                let center = (position.0 .0 as f32 + offset, position.0 .1 as f32 + offset);
+
+                for i in 0..count {
+                    let entity = spawn_projectile(
+                        &mut commands,
+                        &node_size,
+                        &projectile_sprites,
+                        center,
+                        i as f32 * TAU / count as f32,
+                        damage,
+                        Faction::Enemy,
+                    );
+
+                    if blast > 0 {
+                        commands.entity(entity).insert(Explosive(blast));
+                    }
+                }
+            }
+            BossPattern::Summon { count } => {
+                let rng = &mut game_rng.behaviour;
+                let (row, col) = position.0;
+                let mut around: Vec<Coordinates> = (row.saturating_sub(1)..=row + footprint)
+                    .flat_map(|r| (col.saturating_sub(1)..=col + footprint).map(move |c| (r, c)))
+                    .filter(|it| {
+                        reach(&position.0, footprint, it) > 0
+                            && matrix.contains(it)
+                            && !matrix[*it].is_wall()
+                    })
+                    .collect();
+
+                around.shuffle(rng);
+
+                for start in around.into_iter().take(count) {
+                    if let Some((handle, minion)) = enemy_archetypes.pick(rng) {
+                        spawn_archetype(
+                            &mut commands,
+                            &node_size,
+                            &enemy_settings,
+                            (handle, minion),
+                            start,
+                            start,
+                            rng,
+                        );
+                    }
+                }
+            }
+            BossPattern::Charge { speed, time } => {
+                if charging.is_some() {
+                    continue;
+                }
+
+                let step_time = walk.duration();
+
+                walk.set_duration(step_time.div_f32(speed.max(0.01)));
+                commands.entity(entity).insert(Charging {
+                    timer: Timer::from_seconds(time, TimerMode::Once),
+                    step_time,
+                });
+            }
+        }
+    }
+}
+
+fn charge_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Charging, &mut WalkAnimationTimer)>,
+) {
+    for (entity, mut charging, mut walk) in &mut query {
+        charging.timer.tick(time.delta());
+
+        if charging.timer.finished() {
+            walk.set_duration(charging.step_time);
+            commands.entity(entity).remove::<Charging>();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn render_bar_system(
+    enemy_archetypes: Res<EnemyArchetypes>,
+    query: Query<(&Health, &Behaviour, &EnemyType), With<Boss>>,
+    mut bar_query: Query<&mut Visibility, With<BossBar>>,
+    mut name_query: Query<&mut Text, With<BossName>>,
+    mut fill_query: Query<&mut Style, With<BossFill>>,
+) {
+    let boss = query.iter().next();
+
+    for mut visibility in &mut bar_query {
+        visibility.is_visible = boss.is_some();
+    }
+
+    let (health, behaviour, enemy_type) = match boss {
+        Some(boss) => boss,
+        None => return,
+    };
+
+    for mut text in &mut name_query {
+        let name = enemy_archetypes
+            .get(&enemy_type.archetype)
+            .map_or("", |it| it.name.as_str());
+
+        if text.sections[0].value != name {
+            text.sections[0].value = name.to_string();
+        }
+    }
+
+    for mut style in &mut fill_query {
+        let share = health.0 as f32 / behaviour.max_health.max(1) as f32;
+
+        style.size.width = Val::Percent(100. * share.clamp(0., 1.));
+    }
+}
@@ -0,0 +1,193 @@
+use bevy::{input::mouse::MouseWheel, prelude::*, transform::TransformSystem};
+
+use crate::{game::coordinates::Coordinates, GameMode, LivePosition, NodeSize};
+
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.;
+// per notch of the mouse wheel
+const ZOOM_STEP: f32 = 1.1;
+// higher catches up with the player faster
+const FOLLOW_SPEED: f32 = 6.;
+// further than this many cells away, the camera jumps to the player instead
+const SNAP_DISTANCE: f32 = 32.;
+
+// focus is the (row, col) in the middle of the screen, zoom is the size of a screen pixel
+// in world pixels, so values above 1 zoom out
+#[derive(Resource)]
+pub struct GameCamera {
+    pub focus: (f32, f32),
+    pub zoom: f32,
+    pub follow: bool,
+}
+
+impl Default for GameCamera {
+    fn default() -> Self {
+        Self {
+            focus: (0., 0.),
+            zoom: 1.,
+            follow: true,
+        }
+    }
+}
+
+impl GameCamera {
+    // the camera sits on the focus, window pixels grow with the zoom,
+    // the window is passed in so that resizing is picked up right away
+    pub fn screen_to_world(
+        &self,
+        window: &Window,
+        node_size: &NodeSize,
+        live_position: &LivePosition,
+        screen: Vec2,
+    ) -> Vec2 {
+        let center = Vec2::new(window.width(), window.height()) / 2.;
+
+        node_size.cell_corner(live_position, self.focus) + (screen - center) * self.zoom
+    }
+
+    pub fn screen_to_cell(
+        &self,
+        window: &Window,
+        node_size: &NodeSize,
+        live_position: &LivePosition,
+        screen: Vec2,
+    ) -> Option<Coordinates> {
+        let world = self.screen_to_world(window, node_size, live_position, screen);
+        let (row, col) = node_size.world_to_cell(live_position, world);
+
+        (row >= 0. && col >= 0.).then_some((row.floor() as usize, col.floor() as usize))
+    }
+
+    // the (row, col) corners of the screen, top left first, in fractional cells
+    pub fn visible_cells(&self, window: &Window, node_size: &NodeSize) -> ((f32, f32), (f32, f32)) {
+        let rows = window.height() * self.zoom / node_size.0 .1 / 2.;
+        let cols = window.width() * self.zoom / node_size.0 .0 / 2.;
+
+        (
+            (self.focus.0 - rows, self.focus.1 - cols),
+            (self.focus.0 + rows, self.focus.1 + cols),
+        )
+    }
+
+    pub fn cursor_cell(
+        &self,
+        window: &Window,
+        node_size: &NodeSize,
+        live_position: &LivePosition,
+    ) -> Option<Coordinates> {
+        window
+            .cursor_position()
+            .and_then(|it| self.screen_to_cell(window, node_size, live_position, it))
+    }
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameCamera>()
+            .add_system(zoom_system)
+            .add_system(pan_system)
+            .add_system(follow_system.after(pan_system))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_camera_system.before(TransformSystem::TransformPropagate),
+            );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn zoom_system(mut wheel_events: EventReader<MouseWheel>, mut camera: ResMut<GameCamera>) {
+    for event in wheel_events.iter() {
+        camera.zoom = (camera.zoom * ZOOM_STEP.powf(-event.y)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+}
+
+// the editor paints walls with the right mouse button, so it pans with the middle one
+fn pan_system(
+    windows: Res<Windows>,
+    mouse: Res<Input<MouseButton>>,
+    state: Res<State<GameMode>>,
+    node_size: Res<NodeSize>,
+    mut camera: ResMut<GameCamera>,
+    mut last_cursor: Local<Option<Vec2>>,
+) {
+    let button = match state.current() {
+        GameMode::Playing | GameMode::GameOver => MouseButton::Right,
+        GameMode::Editing => MouseButton::Middle,
+    };
+    let cursor = windows.get_primary().and_then(|it| it.cursor_position());
+
+    if !mouse.pressed(button) || cursor.is_none() {
+        *last_cursor = None;
+
+        return;
+    }
+
+    if let (Some(last), Some(cursor)) = (*last_cursor, cursor) {
+        let delta = cursor - last;
+
+        if delta != Vec2::ZERO {
+            camera.follow = false;
+            camera.focus.0 += delta.y * camera.zoom / node_size.0 .1;
+            camera.focus.1 -= delta.x * camera.zoom / node_size.0 .0;
+        }
+    }
+
+    *last_cursor = cursor;
+}
+
+fn follow_system(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut camera: ResMut<GameCamera>,
+    p_query: Query<&LivePosition>,
+) {
+    if keys.just_pressed(KeyCode::F) {
+        camera.follow = !camera.follow;
+    }
+
+    if !camera.follow || p_query.is_empty() {
+        return;
+    }
+
+    let live_position = p_query.single().0;
+    let distance = (live_position.0 - camera.focus.0)
+        .abs()
+        .max((live_position.1 - camera.focus.1).abs());
+
+    // raw time, so that the camera keeps moving while the simulation is paused
+    let t = match distance > SNAP_DISTANCE {
+        true => 1.,
+        false => 1. - (-FOLLOW_SPEED * time.raw_delta_seconds()).exp(),
+    };
+
+    camera.focus.0 += (live_position.0 - camera.focus.0) * t;
+    camera.focus.1 += (live_position.1 - camera.focus.1) * t;
+}
+
+// world transforms are laid out around the player, so the camera sits at the focus relative to it
+fn apply_camera_system(
+    camera: Res<GameCamera>,
+    node_size: Res<NodeSize>,
+    p_query: Query<&LivePosition>,
+    mut query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+) {
+    if p_query.is_empty() {
+        return;
+    }
+
+    let corner = node_size.cell_corner(p_query.single(), camera.focus);
+
+    for (mut transform, mut projection) in &mut query {
+        transform.translation.x = corner.x;
+        transform.translation.y = corner.y;
+
+        if projection.scale != camera.zoom {
+            projection.scale = camera.zoom;
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use bevy::prelude::*;
+
+use crate::{
+    game::{
+        chunk::{chunk_of, chunk_origin},
+        coordinates::Coordinates,
+        door::{neighbour, set_edge, AgentRules, Barrier, Doors, KeyColor},
+        level::{Level, Placement, PlacementKind},
+        matrix::Matrix,
+        node::{Entry, Node},
+    },
+    GameMode, LivePosition, NodeSize, Player, PlayerPosition,
+};
+
+use super::grid::ChunkStore;
+
+// share of the cell a closed door or gate covers along its edge
+const BAR_THICKNESS: f32 = 0.2;
+// share of the cell covered by keys and switches
+const ITEM_SIZE: f32 = 0.4;
+
+const DOOR_COLOR: Color = Color::rgb(0.45, 0.3, 0.15);
+const GATE_COLOR: Color = Color::rgb(0.55, 0.55, 0.6);
+const SWITCH_COLOR: Color = Color::rgb(0.3, 0.3, 0.35);
+
+// everything on the floor that belongs to doors and gates, closed edges only show while closed
+#[derive(Component)]
+struct DoorMarker {
+    placement: Placement,
+    edge: Option<Entry>,
+}
+
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Doors>()
+            .add_system(update_doors_system)
+            .add_system_set(
+                SystemSet::on_update(GameMode::Playing)
+                    .with_system(open_doors_system.after(update_doors_system))
+                    .with_system(pick_up_system.after(update_doors_system)),
+            )
+            .add_system(spawn_markers_system)
+            .add_system(render_markers_system.after(spawn_markers_system));
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+pub(crate) fn key_color(color: KeyColor) -> Color {
+    match color {
+        KeyColor::Red => Color::rgb(0.9, 0.2, 0.2),
+        KeyColor::Green => Color::rgb(0.2, 0.8, 0.3),
+        KeyColor::Blue => Color::rgb(0.2, 0.4, 0.95),
+        KeyColor::Yellow => Color::rgb(0.95, 0.85, 0.2),
+    }
+}
+
+// placements that belong to doors and gates, markers also show keys and switches
+fn barriers(level: &Level, with_items: bool) -> Vec<Placement> {
+    level
+        .placements
+        .iter()
+        .filter(|it| match it.kind {
+            PlacementKind::Door { .. } | PlacementKind::Gate { .. } => true,
+            PlacementKind::Key(_) | PlacementKind::Switch(_) => with_items,
+            _ => false,
+        })
+        .copied()
+        .collect()
+}
+
+// the placements that are in one list but not in the other, as (added, removed)
+fn changes(last: &[Placement], current: &[Placement]) -> (Vec<Placement>, Vec<Placement>) {
+    (
+        current
+            .iter()
+            .filter(|it| !last.contains(it))
+            .copied()
+            .collect(),
+        last.iter()
+            .filter(|it| !current.contains(it))
+            .copied()
+            .collect(),
+    )
+}
+
+// only the doors and gates that were added or removed since the last change are touched
+fn update_doors_system(
+    level: Res<Level>,
+    matrix: Res<Matrix<Node>>,
+    mut doors: ResMut<Doors>,
+    mut last: Local<Vec<Placement>>,
+) {
+    if !level.is_changed() {
+        return;
+    }
+
+    let current = barriers(&level, false);
+
+    if current == *last {
+        return;
+    }
+
+    let (added, removed) = changes(&last, &current);
+
+    for placement in &removed {
+        doors.remove(&matrix, placement);
+    }
+
+    for placement in &added {
+        doors.add(&matrix, placement);
+    }
+
+    *last = current;
+}
+
+// a door opens for good as soon as the player walks into it, the placement goes with it
+fn open_doors_system(
+    doors: Res<Doors>,
+    mut matrix: ResMut<Matrix<Node>>,
+    mut level: ResMut<Level>,
+    p_query: Query<(&PlayerPosition, &AgentRules), With<Player>>,
+) {
+    for (player_position, rules) in &p_query {
+        let from = player_position.current_position.0;
+        let to = match player_position.next_position {
+            Some(next_position) => next_position.0,
+            None => continue,
+        };
+        let edge = match doors.crossed(&from, &to) {
+            Some((edge, barrier @ Barrier::Door(_))) if rules.can_pass(&barrier) => edge,
+            _ => continue,
+        };
+        let door = level.placements.iter().position(|it| match it.kind {
+            PlacementKind::Door {
+                edge: door_edge, ..
+            } => {
+                (it.coordinates == from && door_edge == edge)
+                    || (it.coordinates == to && door_edge == edge.opposite())
+            }
+            _ => false,
+        });
+
+        if let Some(index) = door {
+            let placement = level.placements.remove(index);
+
+            if let PlacementKind::Door { edge, .. } = placement.kind {
+                set_edge(&mut matrix, placement.coordinates, edge, true);
+            }
+        }
+    }
+}
+
+// keys are taken along, switches toggle every gate of their channel each time they are entered
+fn pick_up_system(
+    chunk_store: Res<ChunkStore>,
+    mut matrix: ResMut<Matrix<Node>>,
+    mut level: ResMut<Level>,
+    mut p_query: Query<(&PlayerPosition, &mut AgentRules), With<Player>>,
+    mut last_cell: Local<Option<Coordinates>>,
+) {
+    if p_query.is_empty() {
+        return;
+    }
+
+    let (player_position, mut rules) = p_query.single_mut();
+    let cell = player_position.current_position.0;
+
+    if *last_cell == Some(cell) {
+        return;
+    }
+
+    *last_cell = Some(cell);
+
+    let kinds: Vec<PlacementKind> = level
+        .placements
+        .iter()
+        .filter(|it| it.coordinates == cell)
+        .map(|it| it.kind)
+        .collect();
+
+    for kind in kinds {
+        match kind {
+            PlacementKind::Key(color) => {
+                rules.keys.insert(color);
+                level.remove(kind, cell);
+
+                info!("picked up the {:?} key", color);
+            }
+            PlacementKind::Switch(channel) => {
+                let gates: Vec<(Coordinates, Entry)> = level
+                    .placements
+                    .iter()
+                    .filter_map(|it| match it.kind {
+                        PlacementKind::Gate {
+                            edge,
+                            channel: gate_channel,
+                        } if gate_channel == channel => Some((it.coordinates, edge)),
+                        _ => None,
+                    })
+                    .collect();
+
+                for (coordinates, edge) in gates {
+                    toggle_gate(&mut matrix, &chunk_store, coordinates, edge);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// every edge the gate closes is flipped, in the store for cells of chunks that are not loaded
+fn toggle_gate(
+    matrix: &mut Matrix<Node>,
+    chunk_store: &ChunkStore,
+    coordinates: Coordinates,
+    edge: Entry,
+) {
+    let halves: Vec<(Coordinates, Entry)> = [edge, edge.opposite()]
+        .into_iter()
+        .flat_map(|edge| {
+            std::iter::once((coordinates, edge))
+                .chain(neighbour(matrix, &coordinates, &edge).map(|it| (it, edge.opposite())))
+        })
+        .collect();
+
+    for (cell, edge) in halves {
+        let chunk = chunk_of(&cell);
+
+        if matrix.is_chunk_loaded(&chunk) {
+            let mut node = matrix[cell];
+
+            node[edge] = !node[edge];
+            matrix.set(cell, node);
+
+            continue;
+        }
+
+        let origin = chunk_origin(&chunk);
+        let local = (cell.0 - origin.0, cell.1 - origin.1);
+        let result = chunk_store.edit(&chunk, |chunk_matrix, _| {
+            chunk_matrix[local][edge] = !chunk_matrix[local][edge];
+        });
+
+        if let Err(error) = result {
+            warn!("could not toggle the gate in chunk {:?}: {}", chunk, error);
+        }
+    }
+}
+
+fn spawn_markers_system(
+    mut commands: Commands,
+    level: Res<Level>,
+    node_size: Res<NodeSize>,
+    query: Query<(Entity, &DoorMarker)>,
+    mut last: Local<Vec<Placement>>,
+) {
+    if !level.is_changed() {
+        return;
+    }
+
+    let current = barriers(&level, true);
+
+    if current == *last {
+        return;
+    }
+
+    let (added, removed) = changes(&last, &current);
+
+    for (entity, marker) in &query {
+        if removed.contains(&marker.placement) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    let (width, height) = node_size.0;
+    let bar = |edge: Entry| match edge {
+        Entry::LEFT | Entry::RIGHT => Vec2::new(width * BAR_THICKNESS, height),
+        Entry::TOP | Entry::BOTTOM => Vec2::new(width, height * BAR_THICKNESS),
+    };
+    let item = Vec2::new(width * ITEM_SIZE, height * ITEM_SIZE);
+
+    for placement in added {
+        let (color, size, edges) = match placement.kind {
+            PlacementKind::Key(color) => (key_color(color), item, vec![None]),
+            PlacementKind::Switch(_) => (SWITCH_COLOR, item, vec![None]),
+            PlacementKind::Door { edge, lock } => (
+                lock.map_or(DOOR_COLOR, key_color),
+                bar(edge),
+                vec![Some(edge)],
+            ),
+            PlacementKind::Gate { edge, .. } => (
+                GATE_COLOR,
+                bar(edge),
+                vec![Some(edge), Some(edge.opposite())],
+            ),
+            _ => continue,
+        };
+
+        for edge in edges {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(size),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0., 0., 99.6),
+                    ..default()
+                },
+                DoorMarker { placement, edge },
+            ));
+        }
+    }
+
+    *last = current;
+}
+
+fn render_markers_system(
+    node_size: Res<NodeSize>,
+    matrix: Res<Matrix<Node>>,
+    p_query: Query<&LivePosition>,
+    mut query: Query<(&DoorMarker, &mut Transform, &mut Visibility)>,
+) {
+    let (width, height) = node_size.0;
+
+    for live_position in &p_query {
+        for (marker, mut transform, mut visibility) in &mut query {
+            let coordinates = marker.placement.coordinates;
+            let center =
+                node_size.cell_center(live_position, (coordinates.0 as f32, coordinates.1 as f32));
+            let offset = match marker.edge {
+                Some(Entry::LEFT) => Vec2::new(-width * (1. - BAR_THICKNESS) / 2., 0.),
+                Some(Entry::TOP) => Vec2::new(0., height * (1. - BAR_THICKNESS) / 2.),
+                Some(Entry::RIGHT) => Vec2::new(width * (1. - BAR_THICKNESS) / 2., 0.),
+                Some(Entry::BOTTOM) => Vec2::new(0., -height * (1. - BAR_THICKNESS) / 2.),
+                None => Vec2::ZERO,
+            };
+
+            transform.translation.x = center.x + offset.x;
+            transform.translation.y = center.y + offset.y;
+            visibility.is_visible = match marker.edge {
+                Some(edge) => !matrix[marker.placement.coordinates][edge],
+                None => true,
+            };
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use bevy::prelude::*;
+
+use crate::{
+    game::{
+        brush,
+        coordinates::Coordinates,
+        edit_history::{Edit, EditHistory},
+        encoded_matrix::EncodedMatrix,
+        level::{Level, Placement, PlacementKind},
+        matrix::Matrix,
+        node::Node,
+    },
+    GameMode, LivePosition, NodeSize, UserPosition,
+};
+
+use super::{
+    door::key_color,
+    grid::{unload_floor, ChunkStore, DirtyChunks, LoadedChunks, OpenNodes},
+};
+
+const LEVEL_DIRECTORY: &str = "levels";
+const LEVEL_FILE: &str = "levels/editor.level";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorTool {
+    Pencil,
+    Line,
+    Rectangle,
+    FloodFill,
+    Place(PlacementKind),
+}
+
+// left mouse paints floor, right mouse paints walls,
+// line, rectangle and flood fill are applied when the button is released
+#[derive(Resource)]
+pub struct Editor {
+    pub tool: EditorTool,
+    anchor: Option<Coordinates>,
+    paint: Node,
+    stroke: Vec<(Coordinates, Node, Node)>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            tool: EditorTool::Pencil,
+            anchor: None,
+            paint: Node::open(),
+            stroke: Vec::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct EditorText;
+
+#[derive(Component)]
+struct PlacementMarker(Coordinates);
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .init_resource::<EditHistory>()
+            .add_startup_system(setup_system)
+            .add_system(toggle_editor_system)
+            .add_system_set(SystemSet::on_enter(GameMode::Editing).with_system(enter_editor_system))
+            .add_system_set(SystemSet::on_exit(GameMode::Editing).with_system(exit_editor_system))
+            .add_system_set(
+                SystemSet::on_update(GameMode::Editing)
+                    .with_system(select_tool_system)
+                    .with_system(brush_system.after(select_tool_system))
+                    .with_system(history_system.after(brush_system))
+                    .with_system(file_system.after(history_system))
+                    .with_system(render_markers_system.after(file_system))
+                    .with_system(render_text_system.after(select_tool_system)),
+            );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle {
+            visibility: Visibility::INVISIBLE,
+            ..TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("DejaVuSansMono.ttf"),
+                    font_size: 16.,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(8.),
+                    left: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            })
+        },
+        EditorText,
+    ));
+}
+
+fn toggle_editor_system(keys: Res<Input<KeyCode>>, mut state: ResMut<State<GameMode>>) {
+    if keys.just_pressed(KeyCode::Tab) {
+        let next = match state.current() {
+            GameMode::Playing => GameMode::Editing,
+            GameMode::Editing => GameMode::Playing,
+            GameMode::GameOver => return,
+        };
+
+        if let Err(error) = state.set(next) {
+            warn!("could not switch to {:?}: {:?}", next, error);
+        }
+    }
+}
+
+fn enter_editor_system(
+    mut time: ResMut<Time>,
+    mut editor: ResMut<Editor>,
+    mut query: Query<&mut Visibility, With<EditorText>>,
+) {
+    time.pause();
+    editor.set_changed();
+
+    for mut visibility in &mut query {
+        visibility.is_visible = true;
+    }
+}
+
+fn exit_editor_system(
+    mut commands: Commands,
+    mut time: ResMut<Time>,
+    mut editor: ResMut<Editor>,
+    mut history: ResMut<EditHistory>,
+    mut query: Query<&mut Visibility, With<EditorText>>,
+    m_query: Query<Entity, With<PlacementMarker>>,
+) {
+    time.unpause();
+
+    // the history is the editor's alone, playing moves things it knows nothing about
+    editor.stroke.clear();
+    editor.anchor = None;
+    history.clear();
+
+    for mut visibility in &mut query {
+        visibility.is_visible = false;
+    }
+
+    for entity in &m_query {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn select_tool_system(keys: Res<Input<KeyCode>>, mut editor: ResMut<Editor>) {
+    let tool = match keys.get_just_pressed().next() {
+        Some(KeyCode::Key1) => EditorTool::Pencil,
+        Some(KeyCode::Key2) => EditorTool::Line,
+        Some(KeyCode::Key3) => EditorTool::Rectangle,
+        Some(KeyCode::Key4) => EditorTool::FloodFill,
+        Some(KeyCode::Key5) => EditorTool::Place(PlacementKind::PlayerSpawn),
+        Some(KeyCode::Key6) => EditorTool::Place(PlacementKind::EnemySpawn),
+        Some(KeyCode::Key7) => EditorTool::Place(PlacementKind::SpeedPowerUp),
+        Some(KeyCode::Key8) => EditorTool::Place(PlacementKind::ProjectilePowerUp),
+        Some(KeyCode::Key9) => EditorTool::Place(PlacementKind::Exit),
+        _ => return,
+    };
+
+    if editor.tool != tool && editor.anchor.is_none() {
+        editor.tool = tool;
+    }
+}
+
+fn brush_system(
+    mouse: Res<Input<MouseButton>>,
+    mut editor: ResMut<Editor>,
+    mut history: ResMut<EditHistory>,
+    mut matrix: ResMut<Matrix<Node>>,
+    mut level: ResMut<Level>,
+    query: Query<&UserPosition>,
+) {
+    let coordinates = match query.get_single().ok().and_then(|it| it.coordinates) {
+        Some(coordinates) => coordinates,
+        None => return,
+    };
+
+    if let EditorTool::Place(kind) = editor.tool {
+        let place = mouse.just_pressed(MouseButton::Left);
+
+        if place || mouse.just_pressed(MouseButton::Right) {
+            let mut placements = level.placements.clone();
+
+            // one placement per cell, and a single player spawn
+            placements.retain(|it| {
+                it.coordinates != coordinates
+                    && !(place && kind == PlacementKind::PlayerSpawn && it.kind == kind)
+            });
+
+            if place {
+                placements.push(Placement { kind, coordinates });
+            }
+
+            // erasing an empty cell or placing what is already there is no edit
+            if placements != level.placements {
+                history.push(Edit::placements(&level.placements, &placements));
+                level.placements = placements;
+            }
+        }
+
+        return;
+    }
+
+    if mouse.just_pressed(MouseButton::Left) {
+        editor.anchor = Some(coordinates);
+        editor.paint = Node::open();
+    } else if mouse.just_pressed(MouseButton::Right) {
+        editor.anchor = Some(coordinates);
+        editor.paint = Node::closed();
+    }
+
+    let anchor = match editor.anchor {
+        Some(anchor) => anchor,
+        None => return,
+    };
+    let released =
+        mouse.just_released(MouseButton::Left) || mouse.just_released(MouseButton::Right);
+    let cells = match editor.tool {
+        // the line from the previous cell, so that fast strokes leave no gaps
+        EditorTool::Pencil => {
+            editor.anchor = Some(coordinates);
+
+            brush::line(&anchor, &coordinates)
+        }
+        EditorTool::Line if released => brush::line(&anchor, &coordinates),
+        EditorTool::Rectangle if released => brush::rectangle(&anchor, &coordinates),
+        EditorTool::FloodFill if released => brush::flood_fill(&matrix, &anchor),
+        _ => Vec::new(),
+    };
+    let paint = editor.paint;
+
+    // cells of unloaded chunks are not painted and are left out of the stroke
+    for coordinates in cells {
+        if matrix.contains(&coordinates) {
+            let before = matrix[coordinates];
+
+            if matrix.set(coordinates, paint) {
+                editor.stroke.push((coordinates, before, paint));
+            }
+        }
+    }
+
+    if released {
+        let stroke = std::mem::take(&mut editor.stroke);
+
+        editor.anchor = None;
+        history.push(Edit {
+            cells: stroke,
+            ..default()
+        });
+    }
+}
+
+fn history_system(
+    keys: Res<Input<KeyCode>>,
+    editor: Res<Editor>,
+    mut history: ResMut<EditHistory>,
+    mut matrix: ResMut<Matrix<Node>>,
+    mut level: ResMut<Level>,
+) {
+    let control = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    if !control || editor.anchor.is_some() {
+        return;
+    }
+
+    let result = if keys.just_pressed(KeyCode::Y) || (shift && keys.just_pressed(KeyCode::Z)) {
+        history.redo(&mut matrix, &mut level)
+    } else if keys.just_pressed(KeyCode::Z) {
+        history.undo(&mut matrix, &mut level)
+    } else {
+        return;
+    };
+
+    if let Err(cells) = result {
+        warn!(
+            "{} cells of the edit are in chunks that are not loaded, move closer to them first",
+            cells.len()
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+// the level file holds the whole floor, the grid only keeps the chunks around the camera
+fn file_system(
+    keys: Res<Input<KeyCode>>,
+    chunk_store: Res<ChunkStore>,
+    mut history: ResMut<EditHistory>,
+    mut matrix: ResMut<Matrix<Node>>,
+    mut level: ResMut<Level>,
+    mut open_nodes: ResMut<OpenNodes>,
+    mut loaded_chunks: ResMut<LoadedChunks>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+) {
+    if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::S) {
+        dirty_chunks.update(&chunk_store, &matrix, &level);
+        dirty_chunks.flush(&chunk_store, &matrix, &level);
+
+        let (whole_matrix, mut whole_level) = chunk_store.whole_floor(matrix.rows, matrix.cols);
+
+        whole_level.placements = level.placements.clone();
+
+        let result = std::fs::create_dir_all(LEVEL_DIRECTORY)
+            .and_then(|_| EncodedMatrix::from((&whole_matrix, &whole_level)).to_file(LEVEL_FILE));
+
+        match result {
+            Ok(_) => info!("saved level to {}", LEVEL_FILE),
+            Err(error) => warn!("could not save level to {}: {}", LEVEL_FILE, error),
+        }
+    } else if keys.just_pressed(KeyCode::O) {
+        if !std::path::Path::new(LEVEL_FILE).exists() {
+            warn!("no level saved at {}", LEVEL_FILE);
+
+            return;
+        }
+
+        let (loaded_matrix, loaded_level): (Matrix<Node>, Level) =
+            match EncodedMatrix::from_file(LEVEL_FILE) {
+                Ok(encoded) => encoded.into(),
+                Err(error) => {
+                    warn!("could not load level from {}: {}", LEVEL_FILE, error);
+
+                    return;
+                }
+            };
+
+        if (loaded_matrix.rows, loaded_matrix.cols) != (matrix.rows, matrix.cols) {
+            warn!(
+                "level {} is {}x{}, the grid is {}x{}",
+                LEVEL_FILE, loaded_matrix.rows, loaded_matrix.cols, matrix.rows, matrix.cols
+            );
+
+            return;
+        }
+
+        // the chunks on disk that were not saved yet are replaced all the same
+        dirty_chunks.update(&chunk_store, &matrix, &level);
+        dirty_chunks.flush(&chunk_store, &matrix, &level);
+
+        if let Err(error) = chunk_store.save_all(&loaded_matrix, &loaded_level) {
+            warn!("could not store level {}: {}", LEVEL_FILE, error);
+
+            return;
+        }
+
+        unload_floor(&mut matrix, &mut level, &mut open_nodes);
+        level.placements = loaded_level.placements;
+        *loaded_chunks = LoadedChunks::default();
+        dirty_chunks.skip(&matrix, &level);
+        history.clear();
+
+        info!("loaded level from {}", LEVEL_FILE);
+    }
+}
+
+fn render_markers_system(
+    mut commands: Commands,
+    node_size: Res<NodeSize>,
+    level: Res<Level>,
+    p_query: Query<&LivePosition>,
+    mut query: Query<(Entity, &PlacementMarker, &mut Transform)>,
+) {
+    if level.is_changed() || query.iter().count() != level.placements.len() {
+        for (entity, _, _) in &query {
+            commands.entity(entity).despawn();
+        }
+
+        for placement in &level.placements {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: marker_color(&placement.kind),
+                        custom_size: Some(Vec2::new(node_size.0 .0 / 2., node_size.0 .1 / 2.)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0., 0., 101.),
+                    ..default()
+                },
+                PlacementMarker(placement.coordinates),
+            ));
+        }
+
+        return;
+    }
+
+    for live_position in &p_query {
+        for (_, marker, mut transform) in &mut query {
+            let center =
+                node_size.cell_center(live_position, (marker.0 .0 as f32, marker.0 .1 as f32));
+
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
+        }
+    }
+}
+
+fn marker_color(kind: &PlacementKind) -> Color {
+    match kind {
+        PlacementKind::PlayerSpawn => Color::rgba(0.2, 1.0, 0.2, 0.8),
+        PlacementKind::EnemySpawn => Color::rgba(1.0, 0.2, 0.2, 0.8),
+        PlacementKind::SpeedPowerUp => Color::rgba(0.2, 0.6, 1.0, 0.8),
+        PlacementKind::ProjectilePowerUp => Color::rgba(1.0, 0.6, 0.2, 0.8),
+        PlacementKind::Exit => Color::rgba(0.8, 0.2, 1.0, 0.8),
+        PlacementKind::Entrance => Color::rgba(1.0, 1.0, 1.0, 0.8),
+        PlacementKind::Key(color)
+        | PlacementKind::Door {
+            lock: Some(color), ..
+        } => *key_color(*color).set_a(0.8),
+        PlacementKind::Door { lock: None, .. } => Color::rgba(0.6, 0.4, 0.2, 0.8),
+        PlacementKind::Gate { .. } | PlacementKind::Switch(_) => Color::rgba(0.5, 0.5, 0.5, 0.8),
+    }
+}
+
+fn render_text_system(editor: Res<Editor>, mut query: Query<&mut Text, With<EditorText>>) {
+    if !editor.is_changed() {
+        return;
+    }
+
+    for mut text in &mut query {
+        text.sections[0].value = format!(
+            "editor: {:?}\n\
+             1-4 pencil/line/rectangle/fill, 5-9 placements\n\
+             ctrl+z/y undo/redo, ctrl+s/o save/load, tab to play",
+            editor.tool
+        );
+    }
+}
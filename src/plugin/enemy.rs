@@ -1,5 +1,6 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
     sync::{Arc, Mutex},
 };
 
@@ -7,17 +8,42 @@ use bevy::prelude::*;
 use rand::prelude::*;
 
 use crate::{
+    game::{
+        archetype::{EnemyArchetype, EnemyArchetypeLoader, MovementMode},
+        behaviour::{flee_target, line_of_sight, roam_target, BehaviourState, Senses},
+        coordinates::Coordinates,
+        door::{AgentGrid, AgentRules, Doors},
+        dungeon::EnemySettings,
+        hazard::{Hazard, SPIKE_DAMAGE},
+        level::Level,
+        matrix::Matrix,
+        rng::GameRng,
+        steering::{
+            clamp_off_walls, is_corridor, separation, SpatialHash, MAX_OFFSET, SEPARATION_RADIUS,
+        },
+    },
     game::{
         astar::{manhattan_heuristic, AStar},
-        node::{Entry, Node},
+        node::Node,
     },
-    game::{coordinates::Coordinates, matrix::Matrix},
-    Durability, EndPosition, EnemyCount, EnemySprites, EnemyType, EnemyTypeValue, FragSprites,
-    Health, LivePosition, NodeSize, Path, Player, PlayerPosition, Position, ProjectilePosition,
-    TraversalIndex,
+    Durability, EndPosition, EnemyArchetypes, EnemyType, Faction, FragSprites, GameMode, Health,
+    LivePosition, NodeSize, Path, Player, PlayerPosition, Position, ProjectilePosition,
+    ProjectileSprites, TraversalIndex,
 };
 
-use super::grid::OpenNodes;
+use super::{
+    floor::FloorChanged, grid::LoadedChunks, player::PlayerHit, projectile::spawn_projectile,
+};
+
+// share of a cell each enemy keeps to one side of its path, (-max, max)
+const LATERAL_SPREAD: f32 = 0.15;
+// how fast the crowd pushes an offset out and how fast it settles back, in cells per second
+const PUSH_SPEED: f32 = 3.;
+const SETTLE_SPEED: f32 = 1.5;
+// seconds an enemy waits in line in a corridor before squeezing past anyway
+const QUEUE_PATIENCE: f32 = 1.5;
+// spreads the pushes of enemies stacked right on top of each other
+const GOLDEN_ANGLE: f32 = 2.399_963;
 
 #[derive(Bundle)]
 struct PathInstructionsBundle {
@@ -34,27 +60,77 @@ struct PathBundle {
 #[derive(Component)]
 struct CheckPath(bool);
 
+// home is where the enemy spawned, patrols go back and forth between home and the patrol cell,
+// which is picked on the first patrol
+#[derive(Component)]
+pub(crate) struct Behaviour {
+    pub(crate) state: BehaviourState,
+    home: Coordinates,
+    patrol: Coordinates,
+    target: Coordinates,
+    rest: Timer,
+    pub(crate) max_health: u16,
+}
+
+// sent for every enemy that dies
+pub struct EnemyDied {
+    pub archetype: Handle<EnemyArchetype>,
+    pub position: Coordinates,
+}
+
+// cooldown of ranged archetypes, it only runs while attacking
+#[derive(Component, Deref, DerefMut)]
+struct Shooter(Timer);
+
 #[derive(Component, Deref, DerefMut)]
 struct AnimationTimer(Timer);
 
 #[derive(Component, Deref, DerefMut)]
-struct WalkAnimationTimer(Timer);
+pub(crate) struct WalkAnimationTimer(Timer);
 
 #[derive(Component)]
 struct FraggedAt((f32, f32));
 
+// the crowd pushes the offset around while the lateral keeps the enemy to one side of its path,
+// at is where the sprite was drawn last and queued the seconds spent waiting in line
+#[derive(Component)]
+struct Steering {
+    at: (f32, f32),
+    offset: (f32, f32),
+    lateral: f32,
+    queued: Option<f32>,
+}
+
+// the enemies as drawn last frame, bosses are left out, they neither push nor get pushed
+#[derive(Resource, Default)]
+struct Crowd(SpatialHash);
+
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system_to_stage(StartupStage::PostStartup, setup_system)
-            .add_system(track_player_system)
+        app.init_resource::<EnemySettings>()
+            .init_resource::<Crowd>()
+            .add_event::<EnemyDied>()
+            .add_asset::<EnemyArchetype>()
+            .init_asset_loader::<EnemyArchetypeLoader>()
+            .add_system(sync_archetypes_system)
+            .add_system(change_floor_system)
+            .add_system(behaviour_system)
+            .add_system_set(
+                SystemSet::on_update(GameMode::Playing)
+                    .with_system(contact_damage_system.after(behaviour_system))
+                    .with_system(shoot_system.after(behaviour_system)),
+            )
             .add_system(calc_path)
             .add_system(check_path_after_matrix_change)
-            .add_system(traverse_path.after(calc_path))
+            .add_system(steer_system)
+            .add_system(traverse_path.after(calc_path).after(steer_system))
             .add_system(increment_path_traversal.after(traverse_path))
             .add_system(animate_sprite)
-            .add_system(hit_test_projectiles)
+            .add_system(hazard_system.after(increment_path_traversal))
+            .add_system(despawn_dead_system.after(hazard_system))
+            .add_system(hit_test_projectiles.after(despawn_dead_system))
             .add_system(animate_frag_sprite);
     }
 
@@ -63,40 +139,164 @@ impl Plugin for EnemyPlugin {
     }
 }
 
-fn setup_system(
+fn sync_archetypes_system(
+    mut archetype_events: EventReader<AssetEvent<EnemyArchetype>>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+    mut enemy_archetypes: ResMut<EnemyArchetypes>,
+) {
+    if archetype_events.iter().count() == 0 {
+        return;
+    }
+
+    enemy_archetypes.loaded = archetypes
+        .iter()
+        .map(|(id, archetype)| (archetypes.get_handle(id), archetype.clone()))
+        .collect();
+    enemy_archetypes
+        .loaded
+        .sort_unstable_by(|a, b| a.1.name.cmp(&b.1.name));
+}
+
+#[allow(clippy::type_complexity)]
+// the enemies of the previous floor are left behind, the waves of the new floor are harder
+fn change_floor_system(
+    mut commands: Commands,
+    mut floor_changed_events: EventReader<FloorChanged>,
+    mut enemy_settings: ResMut<EnemySettings>,
+    query: Query<Entity, Or<(With<EnemyType>, With<FraggedAt>)>>,
+) {
+    let floor = match floor_changed_events.iter().last() {
+        Some(event) => event.floor,
+        None => return,
+    };
+
+    for entity in &query {
+        commands.entity(entity).despawn();
+    }
+
+    *enemy_settings = EnemySettings::for_floor(floor);
+}
+
+// attacking enemies next to the player hurt on contact with the damage of their archetype
+fn contact_damage_system(
+    enemy_archetypes: Res<EnemyArchetypes>,
+    mut hit_events: EventWriter<PlayerHit>,
+    p_query: Query<&PlayerPosition, With<Player>>,
+    query: Query<(&Position, &EnemyType, &Behaviour)>,
+) {
+    let player = match p_query.get_single() {
+        Ok(player_position) => player_position.current_position.0,
+        Err(_) => return,
+    };
+
+    for (position, enemy_type, behaviour) in &query {
+        if behaviour.state != BehaviourState::Attack {
+            continue;
+        }
+
+        if let Some(archetype) = enemy_archetypes.get(&enemy_type.archetype) {
+            if archetype.damage > 0 && reach(&position.0, archetype.footprint(), &player) <= 1 {
+                hit_events.send(PlayerHit {
+                    damage: archetype.damage,
+                    from: position.0,
+                });
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+// shooters fire at the player whenever the cooldown runs out while they see them
+fn shoot_system(
     mut commands: Commands,
-    enemy_count: Res<EnemyCount>,
+    time: Res<Time>,
+    matrix: Res<Matrix<Node>>,
     node_size: Res<NodeSize>,
-    open_nodes: Res<OpenNodes>,
-    enemy_sprites: Res<EnemySprites>,
+    projectile_sprites: Res<ProjectileSprites>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    p_query: Query<&PlayerPosition, With<Player>>,
+    mut query: Query<(&Position, &EnemyType, &Behaviour, &mut Shooter)>,
 ) {
-    let mut rng = rand::thread_rng();
+    let player = match p_query.get_single() {
+        Ok(player_position) => player_position.current_position.0,
+        Err(_) => return,
+    };
+
+    for (position, enemy_type, behaviour, mut shooter) in &mut query {
+        if behaviour.state != BehaviourState::Attack {
+            continue;
+        }
+
+        shooter.tick(time.delta());
+
+        let ranged = match enemy_archetypes
+            .get(&enemy_type.archetype)
+            .and_then(|it| it.ranged)
+        {
+            Some(ranged) => ranged,
+            None => continue,
+        };
+
+        if !shooter.just_finished() || !line_of_sight(&matrix, &position.0, &player) {
+            continue;
+        }
 
-    (0..enemy_count.0).for_each(|_| {
-        let index = (rng.gen::<f32>() * 24.) as usize;
+        let from = (position.0 .0 as f32, position.0 .1 as f32);
+        let angle = (player.1 as f32 - from.1).atan2(player.0 as f32 - from.0) + PI;
 
-        spawn_enemy(
+        spawn_projectile(
             &mut commands,
-            (index, 49),
             &node_size,
-            &open_nodes,
-            &enemy_sprites,
+            &projectile_sprites,
+            from,
+            angle,
+            ranged.damage,
+            Faction::Enemy,
         );
-    });
+    }
 }
 
 fn check_path_after_matrix_change(
-    n_query: Query<(&Position, &Node), Changed<Node>>,
+    matrix: Res<Matrix<Node>>,
+    mut generation: Local<Option<u64>>,
     mut query: Query<(&Path, &TraversalIndex, &mut CheckPath), With<EnemyType>>,
 ) {
-    for (position, node) in &n_query {
+    let last_generation = match *generation {
+        Some(value) => value,
+        None => {
+            *generation = Some(matrix.changes.generation());
+
+            return;
+        }
+    };
+
+    if matrix.changes.generation() == last_generation {
+        return;
+    }
+
+    *generation = Some(matrix.changes.generation());
+
+    let cells = match matrix.changes.since(last_generation) {
+        Some(cells) => cells,
+        None => {
+            for (_, _, mut check_path) in &mut query {
+                *check_path = CheckPath(true);
+            }
+
+            return;
+        }
+    };
+
+    for position in cells {
+        let node = matrix[position];
+
         for (path, traversal_index, mut check_path) in &mut query {
             let no_path = path.0.is_none() || traversal_index.0.is_none();
 
-            let affects_path = match node[Entry::LEFT] {
-                true => true,
-                false => match &path.0 {
-                    Some(path) => path.contains(&position.0),
+            let affects_path = match node.is_wall() {
+                false => true,
+                true => match &path.0 {
+                    Some(path) => path.contains(&position),
                     _ => false,
                 },
             };
@@ -108,19 +308,42 @@ fn check_path_after_matrix_change(
     }
 }
 
+// partial paths by the way an agent moves, its footprint and the goal they lead to, kept
+// until the cells, hazards, doors or loaded chunks change
+type PartialPaths =
+    HashMap<(MovementMode, usize, Coordinates), HashMap<Coordinates, Vec<Coordinates>>>;
+
+#[allow(clippy::too_many_arguments)]
 fn calc_path(
     matrix: Res<Matrix<Node>>,
+    level: Res<Level>,
+    doors: Res<Doors>,
+    loaded_chunks: Res<LoadedChunks>,
     windows: Res<Windows>,
     node_size: Res<NodeSize>,
+    enemy_archetypes: Res<EnemyArchetypes>,
     mut query: Query<(
         &Position,
         &EndPosition,
         &mut Path,
         &mut TraversalIndex,
         &mut CheckPath,
+        &AgentRules,
+        &EnemyType,
     )>,
+    partial_paths: Local<Arc<Mutex<PartialPaths>>>,
+    mut generations: Local<(u64, u64)>,
 ) {
-    let partial_paths = Arc::new(Mutex::new(HashMap::new()));
+    let current = (
+        matrix.changes.generation(),
+        level.hazard.changes.generation(),
+    );
+
+    if *generations != current || doors.is_changed() || loaded_chunks.is_changed() {
+        partial_paths.lock().unwrap().clear();
+        *generations = current;
+    }
+
     let window = windows.primary();
     let g_w = window.width() / node_size.0 .0;
     let g_h = window.height() / node_size.0 .1;
@@ -128,7 +351,15 @@ fn calc_path(
 
     query.par_for_each_mut(
         64,
-        |(current_position, end_position, mut path, mut traversal_index, mut check_path)| {
+        |(
+            current_position,
+            end_position,
+            mut path,
+            mut traversal_index,
+            mut check_path,
+            rules,
+            enemy_type,
+        )| {
             let start_position = if let (Some(path), Some(index)) = (&path.0, &traversal_index.0) {
                 if *index < path.len() - 1 {
                     path[index + 1]
@@ -146,11 +377,28 @@ fn calc_path(
                     *traversal_index = TraversalIndex(None);
                     *check_path = CheckPath(false);
                 } else {
-                    let d_p = matrix.astar(
+                    // every enemy shares the same rules, but only walkers weigh the hazards, so
+                    // partial paths are shared between enemies that move the same way
+                    let archetype = enemy_archetypes.get(&enemy_type.archetype);
+                    let footprint = archetype.map_or(1, |it| it.footprint());
+                    let movement = archetype.map_or(MovementMode::Walk, |it| it.movement);
+                    let grid = AgentGrid::new(&matrix, &doors, rules)
+                        .with_footprint(footprint)
+                        .with_unloaded_open();
+                    let grid = match movement {
+                        MovementMode::Fly => grid,
+                        MovementMode::Walk => grid.with_hazards(&level.hazard),
+                    };
+                    // a partial path only leads to the goal it was found for, and only agents
+                    // of the same size fit the same ways
+                    let partial_paths = partial_paths
+                        .entry((movement, footprint, end_position.0))
+                        .or_default();
+                    let d_p = grid.astar(
                         start_position,
                         end_position.0,
                         &manhattan_heuristic,
-                        &partial_paths,
+                        partial_paths,
                     );
 
                     if let Some(d_p) = &d_p {
@@ -189,25 +437,98 @@ fn calc_path(
     )
 }
 
-fn track_player_system(
-    p_query: Query<&PlayerPosition, (With<Player>, Changed<PlayerPosition>)>,
-    mut query: Query<(&mut EndPosition, &mut CheckPath)>,
+#[allow(clippy::type_complexity)]
+// the state decides where the path leads, the path is only recalculated when that cell changes
+fn behaviour_system(
+    time: Res<Time>,
+    matrix: Res<Matrix<Node>>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    mut game_rng: ResMut<GameRng>,
+    p_query: Query<&PlayerPosition, With<Player>>,
+    mut query: Query<(
+        &Position,
+        &Path,
+        &Health,
+        &EnemyType,
+        &mut Behaviour,
+        &mut EndPosition,
+        &mut CheckPath,
+    )>,
 ) {
-    for position in &p_query {
-        for (mut end_position, mut check_path) in &mut query {
-            if end_position.0 != position.current_position.0 {
-                *end_position = position.current_position.0.into();
+    let player = match p_query.get_single() {
+        Ok(player_position) => player_position.current_position.0,
+        Err(_) => return,
+    };
+
+    for (position, path, health, enemy_type, mut behaviour, mut end_position, mut check_path) in
+        &mut query
+    {
+        let archetype = match enemy_archetypes.get(&enemy_type.archetype) {
+            Some(archetype) => archetype,
+            None => continue,
+        };
+        let config = &archetype.behaviour;
+        let distance = manhattan_heuristic(&position.0, &player);
+
+        behaviour.rest.tick(time.delta());
+
+        let senses = Senses {
+            distance,
+            in_sight: distance <= config.sight_range
+                && (!config.line_of_sight || line_of_sight(&matrix, &position.0, &player)),
+            health: health.0 as f32 / behaviour.max_health.max(1) as f32,
+            arrived: position.0 == behaviour.target || (path.0.is_none() && !check_path.0),
+            rested: behaviour.rest.finished(),
+        };
+        let state = behaviour.state.next(config, &senses);
+        let entered = state != behaviour.state;
+        let rng = &mut game_rng.behaviour;
+
+        behaviour.state = state;
+        behaviour.target = match state {
+            BehaviourState::Idle if entered => {
+                let (min, max) = config.idle_time;
+                let seconds = min + rng.gen::<f32>() * (max - min).max(0.);
+
+                behaviour.rest = Timer::from_seconds(seconds, TimerMode::Once);
+
+                position.0
+            }
+            BehaviourState::Wander if entered => {
+                roam_target(&matrix, &behaviour.home, config.roam_radius, rng)
             }
+            BehaviourState::Patrol if entered => {
+                if behaviour.patrol == behaviour.home {
+                    behaviour.patrol =
+                        roam_target(&matrix, &behaviour.home, config.roam_radius, rng);
+                }
 
-            if !check_path.0 {
-                *check_path = CheckPath(true);
+                match position.0 == behaviour.home {
+                    true => behaviour.patrol,
+                    false => behaviour.home,
+                }
             }
+            // shooters hold their ground while attacking
+            BehaviourState::Attack if archetype.ranged.is_some() => match entered {
+                true => position.0,
+                false => behaviour.target,
+            },
+            BehaviourState::Chase | BehaviourState::Attack => player,
+            BehaviourState::Flee if entered || senses.arrived => {
+                flee_target(&matrix, &position.0, &player, config.roam_radius, rng)
+            }
+            _ => behaviour.target,
+        };
+
+        if end_position.0 != behaviour.target {
+            *end_position = behaviour.target.into();
+            *check_path = CheckPath(true);
         }
     }
 }
 
 fn increment_path_traversal(
-    enemy_sprites: Res<EnemySprites>,
+    enemy_archetypes: Res<EnemyArchetypes>,
     mut query: Query<
         (
             &Path,
@@ -230,10 +551,9 @@ fn increment_path_traversal(
                     *current_position = p[index].into();
 
                     if index < p.len() - 2 {
-                        if let Some(handle) =
-                            enemy_sprites.find(&p[index], &p[index + 1], &enemy_type.type_value)
-                        {
-                            *texture_atlas_handle = handle;
+                        if let Some(archetype) = enemy_archetypes.get(&enemy_type.archetype) {
+                            *texture_atlas_handle =
+                                archetype.sprites.find(&p[index], &p[index + 1]);
                         }
                     }
                 }
@@ -242,15 +562,69 @@ fn increment_path_traversal(
     }
 }
 
+// every enemy is pushed away from the ones around it and settles back onto its path once alone
+fn steer_system(
+    time: Res<Time>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    mut crowd: ResMut<Crowd>,
+    mut query: Query<(Entity, &EnemyType, &mut Steering)>,
+) {
+    let steered = |enemy_type: &EnemyType| {
+        enemy_archetypes
+            .get(&enemy_type.archetype)
+            .is_none_or(|it| it.footprint() == 1)
+    };
+
+    crowd.0.clear();
+
+    for (entity, enemy_type, steering) in &query {
+        if steered(enemy_type) {
+            crowd.0.insert(entity.index() as usize, steering.at);
+        }
+    }
+
+    let delta = time.delta_seconds();
+
+    for (entity, enemy_type, mut steering) in &mut query {
+        if !steered(enemy_type) {
+            continue;
+        }
+
+        let id = entity.index() as usize;
+        let neighbours = crowd.0.near(id, steering.at, SEPARATION_RADIUS);
+        let push = separation(steering.at, neighbours, id as f32 * GOLDEN_ANGLE);
+        let offset = (
+            steering.offset.0 + (push.0 * PUSH_SPEED - steering.offset.0 * SETTLE_SPEED) * delta,
+            steering.offset.1 + (push.1 * PUSH_SPEED - steering.offset.1 * SETTLE_SPEED) * delta,
+        );
+        let length = offset.0.hypot(offset.1);
+
+        steering.offset = match length > MAX_OFFSET {
+            true => (
+                offset.0 * MAX_OFFSET / length,
+                offset.1 * MAX_OFFSET / length,
+            ),
+            false => offset,
+        };
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn traverse_path(
     time: Res<Time>,
     node_size: Res<NodeSize>,
+    level: Res<Level>,
+    matrix: Res<Matrix<Node>>,
+    crowd: Res<Crowd>,
+    enemy_archetypes: Res<EnemyArchetypes>,
     mut query: Query<(
         &Path,
+        &EnemyType,
         &mut Transform,
         &mut TraversalIndex,
         &mut Visibility,
         &mut WalkAnimationTimer,
+        &mut Steering,
     )>,
     p_query: Query<&LivePosition>,
 ) {
@@ -260,22 +634,56 @@ fn traverse_path(
 
     let live_position = p_query.single();
 
-    for (path, mut transform, mut traversal_index, mut visibility, mut walk_animation_timer) in
-        &mut query
+    for (
+        path,
+        enemy_type,
+        mut transform,
+        mut traversal_index,
+        mut visibility,
+        mut walk_animation_timer,
+        mut steering,
+    ) in &mut query
     {
         let params = (&path.0, traversal_index.0);
-
-        walk_animation_timer.tick(time.delta());
+        let archetype = enemy_archetypes.get(&enemy_type.archetype);
+        let movement = archetype.map(|it| it.movement);
+        let footprint = archetype.map_or(1, |it| it.footprint());
+        // larger sprites sit in the middle of their square
+        let offset = (footprint - 1) as f32 / 2.;
+        let pace = match (params, movement) {
+            (_, Some(MovementMode::Fly)) => 1.,
+            ((Some(path), Some(index)), _) => level.hazard[path[index]].pace(),
+            _ => 1.,
+        };
+
+        walk_animation_timer.tick(time.delta().mul_f32(pace));
 
         if let (Some(path), Some(mut index)) = params {
             if index < path.len() - 1 {
                 let delta_factor: f32;
 
-                if walk_animation_timer.just_finished() {
-                    index += 1;
-                    delta_factor = 0.;
+                if walk_animation_timer.just_finished() || steering.queued.is_some() {
+                    // in corridors the next cell has to be left by whoever is in it first
+                    let queued = footprint == 1
+                        && steering.queued.unwrap_or(0.) < QUEUE_PATIENCE
+                        && path.get(index + 2).is_some_and(|it| {
+                            is_corridor(&matrix, &path[index + 1]) && crowd.0.occupied(it)
+                        });
+
+                    if queued {
+                        steering.queued =
+                            Some(steering.queued.unwrap_or(0.) + time.delta_seconds());
+                        delta_factor = 1.;
+                    } else {
+                        if steering.queued.take().is_some() {
+                            walk_animation_timer.reset();
+                        }
+
+                        index += 1;
+                        delta_factor = 0.;
 
-                    *traversal_index = TraversalIndex(Some(index));
+                        *traversal_index = TraversalIndex(Some(index));
+                    }
                 } else {
                     let elapsed = walk_animation_timer.elapsed().as_millis() as f32;
                     let duration = walk_animation_timer.duration().as_millis() as f32;
@@ -284,38 +692,64 @@ fn traverse_path(
                 }
 
                 let from = path[index];
-                let d_x: f32;
-                let d_y: f32;
-
-                if index < path.len() - 1 {
-                    let to = path[index + 1];
-                    let row_0 = from.0 as f32;
-                    let row_1 = to.0 as f32;
-                    let col_0 = from.1 as f32;
-                    let col_1 = to.1 as f32;
-                    let position = (
-                        row_0 + (row_1 - row_0) * delta_factor,
-                        col_0 + (col_1 - col_0) * delta_factor,
-                    );
+                let to = path.get(index + 1).copied().unwrap_or(from);
+                let position = (
+                    from.0 as f32 + (to.0 as f32 - from.0 as f32) * delta_factor,
+                    from.1 as f32 + (to.1 as f32 - from.1 as f32) * delta_factor,
+                );
+                let center = steer(&matrix, &steering, path, index, position);
 
-                    d_x = (position.1 - live_position.0 .1) * node_size.0 .0 + node_size.0 .0 / 2.;
-                    d_y = (live_position.0 .0 - position.0) * node_size.0 .1 - node_size.0 .1 / 2.;
-                } else {
-                    d_x =
-                        (from.1 as f32 - live_position.0 .1) * node_size.0 .0 + node_size.0 .0 / 2.;
-                    d_y =
-                        (live_position.0 .0 - from.0 as f32) * node_size.0 .1 - node_size.0 .1 / 2.;
-                }
+                steering.at = center;
+                *visibility = Visibility::VISIBLE;
 
-                transform.translation.x = d_x;
-                transform.translation.y = d_y;
+                let center =
+                    node_size.cell_center(live_position, (center.0 + offset, center.1 + offset));
 
-                *visibility = Visibility::VISIBLE;
+                transform.translation.x = center.x;
+                transform.translation.y = center.y;
+            } else if let Some(&last) = path.last() {
+                // arrived enemies still make room for each other
+                let position = (last.0 as f32, last.1 as f32);
+                let center = steer(&matrix, &steering, path, index, position);
+
+                steering.at = center;
+
+                let center =
+                    node_size.cell_center(live_position, (center.0 + offset, center.1 + offset));
+
+                transform.translation.x = center.x;
+                transform.translation.y = center.y;
             }
         }
     }
 }
 
+// the position on the path moved by the crowd and to the side, kept clear of the walls
+fn steer(
+    matrix: &Matrix<Node>,
+    steering: &Steering,
+    path: &[Coordinates],
+    index: usize,
+    position: (f32, f32),
+) -> (f32, f32) {
+    // the side follows the step being walked, or the last one once there
+    let step = match index + 1 < path.len() {
+        true => (path[index], path[index + 1]),
+        false => (path[index.saturating_sub(1)], path[index]),
+    };
+    let direction = (
+        step.1 .0 as f32 - step.0 .0 as f32,
+        step.1 .1 as f32 - step.0 .1 as f32,
+    );
+    let offset = (
+        steering.offset.0 + direction.1 * steering.lateral,
+        steering.offset.1 - direction.0 * steering.lateral,
+    );
+    let offset = clamp_off_walls(matrix, position, offset);
+
+    (position.0 + offset.0, position.1 + offset.1)
+}
+
 fn animate_sprite(
     time: Res<Time>,
     texture_atlases: Res<Assets<TextureAtlas>>,
@@ -359,10 +793,10 @@ fn animate_frag_sprite(
     {
         let live_position = p_query.single();
 
-        transform.translation.x =
-            (fragged_at.0 .1 - live_position.0 .1) * node_size.0 .0 + node_size.0 .0 / 2.;
-        transform.translation.y =
-            (live_position.0 .0 - fragged_at.0 .0) * node_size.0 .1 - node_size.0 .1 / 2.;
+        let center = node_size.cell_center(live_position, fragged_at.0);
+
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
 
         timer.tick(time.delta());
 
@@ -380,43 +814,169 @@ fn animate_frag_sprite(
     }
 }
 
-fn hit_test_projectiles(
+#[allow(clippy::type_complexity)]
+// enemies take the hazard of every cell they step into, the arrival
+// at the other end of a teleporter does not send them back
+fn hazard_system(
+    level: Res<Level>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Position,
+            &mut Health,
+            &mut Path,
+            &mut TraversalIndex,
+            &mut CheckPath,
+            &EnemyType,
+        ),
+        Changed<Position>,
+    >,
+    mut arrived: Local<HashSet<Entity>>,
+) {
+    for (
+        entity,
+        mut position,
+        mut health,
+        mut path,
+        mut traversal_index,
+        mut check_path,
+        enemy_type,
+    ) in &mut query
+    {
+        if arrived.remove(&entity) || !level.hazard.contains(&position.0) {
+            continue;
+        }
+
+        let movement = enemy_archetypes
+            .get(&enemy_type.archetype)
+            .map(|it| it.movement);
+
+        // flyers pass over everything but the teleporters
+        if movement == Some(MovementMode::Fly)
+            && !matches!(level.hazard[position.0], Hazard::Teleporter(_))
+        {
+            continue;
+        }
+
+        match level.hazard[position.0] {
+            Hazard::Spikes => *health = Health(health.0.saturating_sub(SPIKE_DAMAGE)),
+            Hazard::Pit => *health = Health(0),
+            Hazard::Teleporter(_) => {
+                if let Some(exit) = level.teleporter_exit(position.0) {
+                    *position = exit.into();
+                    *path = Path(None);
+                    *traversal_index = TraversalIndex(None);
+                    *check_path = CheckPath(true);
+                    arrived.insert(entity);
+                }
+            }
+            Hazard::None | Hazard::Mud => {}
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+// enemies without health that were not hit by a projectile, those are taken care of on the hit
+fn despawn_dead_system(
     mut commands: Commands,
     node_size: Res<NodeSize>,
-    open_nodes: Res<OpenNodes>,
-    enemy_sprites: Res<EnemySprites>,
     frag_sprites: Res<FragSprites>,
-    mut query: Query<(Entity, &Transform, &mut Durability), With<ProjectilePosition>>,
-    mut e_query: Query<(Entity, &Transform, &mut Health), With<CheckPath>>,
-    p_query: Query<(&PlayerPosition, &LivePosition)>,
+    mut died_events: EventWriter<EnemyDied>,
+    query: Query<(Entity, &Transform, &Health, &Position, &EnemyType)>,
+    p_query: Query<&LivePosition, With<Player>>,
 ) {
-    if p_query.is_empty() {
-        return;
+    let live_position = match p_query.get_single() {
+        Ok(live_position) => live_position,
+        Err(_) => return,
+    };
+
+    for (entity, transform, health, position, enemy_type) in &query {
+        if health.0 > 0 {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        spawn_frag(
+            &mut commands,
+            &node_size,
+            &frag_sprites,
+            live_position,
+            transform,
+        );
+        died_events.send(EnemyDied {
+            archetype: enemy_type.archetype.clone(),
+            position: position.0,
+        });
     }
+}
+
+fn spawn_frag(
+    commands: &mut Commands,
+    node_size: &NodeSize,
+    frag_sprites: &FragSprites,
+    live_position: &LivePosition,
+    enemy_transform: &Transform,
+) {
+    let frag_translation_x =
+        live_position.0 .0 - (enemy_transform.translation.y + node_size.0 .1 / 2.) / node_size.0 .1;
+    let frag_translation_y =
+        (enemy_transform.translation.x - node_size.0 .0 / 2.) / node_size.0 .0 + live_position.0 .1;
+
+    commands.spawn((
+        SpriteSheetBundle {
+            transform: Transform {
+                scale: Vec3::splat(node_size.0 .0 as f32 / frag_sprites.size),
+                translation: enemy_transform.translation,
+                ..default()
+            },
+            texture_atlas: frag_sprites.blood.clone(),
+            ..default()
+        },
+        AnimationTimer(Timer::from_seconds(0.15, TimerMode::Repeating)),
+        FraggedAt((frag_translation_x, frag_translation_y)),
+    ));
+}
 
-    let (player_position, live_position) = p_query.single();
+#[allow(clippy::type_complexity)]
+fn hit_test_projectiles(
+    mut commands: Commands,
+    node_size: Res<NodeSize>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    frag_sprites: Res<FragSprites>,
+    mut died_events: EventWriter<EnemyDied>,
+    mut query: Query<(Entity, &Transform, &mut Durability, &Faction), With<ProjectilePosition>>,
+    mut e_query: Query<(Entity, &Transform, &mut Health, &Position, &EnemyType), With<CheckPath>>,
+    p_query: Query<&LivePosition, With<Player>>,
+) {
+    let live_position = match p_query.get_single() {
+        Ok(live_position) => live_position,
+        Err(_) => return,
+    };
     let mut despawned = Vec::new();
-    let mut spawn_count = 0;
 
-    for (entity, transform, mut p_durability) in &mut query {
-        for (enemy_entity, enemy_transform, mut e_health) in &mut e_query {
+    for (entity, transform, mut p_durability, faction) in &mut query {
+        if *faction == Faction::Enemy {
+            continue;
+        }
+
+        for (enemy_entity, enemy_transform, mut e_health, position, enemy_type) in &mut e_query {
             if despawned.contains(&enemy_entity) {
                 break;
             }
 
+            let footprint = enemy_archetypes
+                .get(&enemy_type.archetype)
+                .map_or(1, |it| it.footprint()) as f32;
             let hit_box = Rect::new(
-                enemy_transform.translation.x - node_size.0 .0 / 2.,
-                enemy_transform.translation.y - node_size.0 .1 / 2.,
-                enemy_transform.translation.x + node_size.0 .0 / 2.,
-                enemy_transform.translation.y + node_size.0 .1 / 2.,
+                enemy_transform.translation.x - footprint * node_size.0 .0 / 2.,
+                enemy_transform.translation.y - footprint * node_size.0 .1 / 2.,
+                enemy_transform.translation.x + footprint * node_size.0 .0 / 2.,
+                enemy_transform.translation.y + footprint * node_size.0 .1 / 2.,
             );
 
             if hit_box.contains(Vec2::new(transform.translation.x, transform.translation.y)) {
-                let frag_translation_x = live_position.0 .0
-                    - (enemy_transform.translation.y + node_size.0 .1 / 2.) / node_size.0 .1;
-                let frag_translation_y = (enemy_transform.translation.x - node_size.0 .0 / 2.)
-                    / node_size.0 .0
-                    + live_position.0 .1;
                 let can_despawn_enemy = e_health.0 > 0;
                 let can_despawn_projectile = p_durability.0 > 0;
 
@@ -429,22 +989,17 @@ fn hit_test_projectiles(
                 if can_despawn_enemy && e_health.0 == 0 {
                     despawned.push(enemy_entity);
                     commands.entity(enemy_entity).despawn();
-
-                    commands.spawn((
-                        SpriteSheetBundle {
-                            transform: Transform {
-                                scale: Vec3::splat(node_size.0 .0 as f32 / frag_sprites.size),
-                                translation: enemy_transform.translation,
-                                ..default()
-                            },
-                            texture_atlas: frag_sprites.blood.clone(),
-                            ..default()
-                        },
-                        AnimationTimer(Timer::from_seconds(0.15, TimerMode::Repeating)),
-                        FraggedAt((frag_translation_x, frag_translation_y)),
-                    ));
-
-                    spawn_count += 1;
+                    spawn_frag(
+                        &mut commands,
+                        &node_size,
+                        &frag_sprites,
+                        live_position,
+                        enemy_transform,
+                    );
+                    died_events.send(EnemyDied {
+                        archetype: enemy_type.archetype.clone(),
+                        position: position.0,
+                    });
                 }
 
                 if can_despawn_projectile && p_durability.0 == 0 {
@@ -453,35 +1008,23 @@ fn hit_test_projectiles(
             }
         }
     }
-
-    for _i in 0..spawn_count {
-        spawn_enemy(
-            &mut commands,
-            player_position.current_position.0,
-            &node_size,
-            &open_nodes,
-            &enemy_sprites,
-        );
-    }
 }
 
-fn spawn_enemy(
+pub(crate) fn spawn_archetype(
     commands: &mut Commands,
+    node_size: &NodeSize,
+    enemy_settings: &EnemySettings,
+    (handle, archetype): (&Handle<EnemyArchetype>, &EnemyArchetype),
+    start_position: Coordinates,
     end_position: Coordinates,
-    node_size: &Res<NodeSize>,
-    open_nodes: &Res<OpenNodes>,
-    enemy_sprites: &Res<EnemySprites>,
-) {
-    let mut rng = rand::thread_rng();
-    let start_position = open_nodes.0[(rng.gen::<f32>() * open_nodes.0.len() as f32) as usize];
-    let rnd = rng.gen_range(0..3);
-    let type_value = match rnd {
-        0 => EnemyTypeValue::Bat,
-        1 => EnemyTypeValue::Spider,
-        _ => EnemyTypeValue::Skeleton,
-    };
-
-    commands
+    rng: &mut StdRng,
+) -> Entity {
+    let (min_scale, max_scale) = archetype.scale;
+    let scale = min_scale + rng.gen::<f32>() * (max_scale - min_scale).max(0.);
+    let health = (archetype.health as f32 * enemy_settings.health_scale).round();
+    let health = health.clamp(1., u16::MAX as f32) as u16;
+
+    let entity = commands
         .spawn(PathInstructionsBundle {
             end_position: end_position.into(),
             current_position: start_position.into(),
@@ -490,16 +1033,35 @@ fn spawn_enemy(
             path: Path(None),
             traversal_index: TraversalIndex(None),
         })
-        .insert(Health(100))
+        .insert(Health(health))
+        .insert(Behaviour {
+            state: BehaviourState::Idle,
+            home: start_position,
+            patrol: start_position,
+            target: start_position,
+            rest: Timer::from_seconds(0., TimerMode::Once),
+            max_health: health,
+        })
         .insert(CheckPath(true))
-        .insert(EnemyType { type_value })
+        .insert(Steering {
+            at: (start_position.0 as f32, start_position.1 as f32),
+            offset: (0., 0.),
+            lateral: match archetype.footprint() {
+                1 => (rng.gen::<f32>() * 2. - 1.) * LATERAL_SPREAD,
+                _ => 0.,
+            },
+            queued: None,
+        })
+        .insert(AgentRules::enemy())
+        .insert(Faction::Enemy)
+        .insert(EnemyType {
+            archetype: handle.clone(),
+        })
         .insert((
             SpriteSheetBundle {
+                texture_atlas: archetype.sprites.down.clone(),
                 transform: Transform {
-                    scale: Vec3::splat(
-                        (rng.gen::<f32>() * 0.5 + 0.75) * node_size.0 .0 as f32
-                            / enemy_sprites.size,
-                    ),
+                    scale: Vec3::splat(scale * node_size.0 .0 / archetype.sprites.size),
                     translation: Vec3 {
                         x: 0.,
                         y: 0.,
@@ -510,10 +1072,35 @@ fn spawn_enemy(
                 visibility: Visibility::INVISIBLE,
                 ..default()
             },
-            AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+            AnimationTimer(Timer::from_seconds(
+                archetype.sprites.frame_time,
+                TimerMode::Repeating,
+            )),
             WalkAnimationTimer(Timer::from_seconds(
-                0.15 + (rng.gen::<f32>() * 1.5),
+                (enemy_settings.step_time + (rng.gen::<f32>() * enemy_settings.step_variance))
+                    / archetype.speed.max(0.01),
                 TimerMode::Repeating,
             )),
-        ));
+        ))
+        .id();
+
+    if let Some(ranged) = archetype.ranged {
+        commands.entity(entity).insert(Shooter(Timer::from_seconds(
+            ranged.cooldown,
+            TimerMode::Repeating,
+        )));
+    }
+
+    entity
+}
+
+// cells between a cell and the nearest cell of the square an enemy covers
+pub(crate) fn reach(anchor: &Coordinates, footprint: usize, cell: &Coordinates) -> i32 {
+    let axis = |from: usize, to: usize| match to {
+        _ if to < from => from - to,
+        _ if to >= from + footprint => to + 1 - from - footprint,
+        _ => 0,
+    };
+
+    (axis(anchor.0, cell.0) + axis(anchor.1, cell.1)) as i32
 }
@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    game::{
+        coordinates::Coordinates,
+        dungeon::{Dungeon, SavedFloor},
+        edit_history::EditHistory,
+        level::{Level, PlacementKind},
+        map_gen::MapGenConfig,
+        matrix::Matrix,
+        node::Node,
+        rng::GameRng,
+    },
+    GameMode, GridSize, LivePosition, NodeSize, Player, PlayerPosition, Position,
+};
+
+use super::{
+    camera::GameCamera,
+    grid::{open_floor, unload_floor, ChunkStore, DirtyChunks, LoadedChunks, OpenNodes},
+};
+
+// pixels per side of the generated stairs image
+const STAIRS_SIZE: usize = 16;
+const STEPS: usize = 4;
+
+// sent once the matrix, level and player have moved to another floor,
+// plugins that own entities on the floor respawn them for the new one
+pub struct FloorChanged {
+    pub floor: usize,
+}
+
+#[derive(Resource)]
+struct StairsTexture(Handle<Image>);
+
+#[derive(Component)]
+struct Stairs(Coordinates);
+
+pub struct FloorPlugin;
+
+impl Plugin for FloorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Dungeon>()
+            .add_event::<FloorChanged>()
+            .add_startup_system(setup_system)
+            .add_startup_system_to_stage(StartupStage::PostStartup, spawn_stairs_system)
+            .add_system_set(SystemSet::on_update(GameMode::Playing).with_system(take_stairs_system))
+            .add_system(change_floor_system.after(take_stairs_system))
+            // stairs placed or erased in the editor show up once playing again
+            .add_system_set(SystemSet::on_exit(GameMode::Editing).with_system(spawn_stairs_system))
+            .add_system(render_stairs_system.after(change_floor_system));
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+// lighter steps towards the top, so that the stairs read as going down into the floor
+fn setup_system(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let step_height = STAIRS_SIZE / STEPS;
+    let data = (0..STAIRS_SIZE)
+        .flat_map(|y| {
+            (0..STAIRS_SIZE).map(move |x| {
+                let edge = x == 0 || x == STAIRS_SIZE - 1;
+                let nose = y % step_height == 0;
+                let shade = match (edge, nose) {
+                    (true, _) => 40,
+                    (false, true) => 220 - (y / step_height * 40) as u8,
+                    (false, false) => 160 - (y / step_height * 35) as u8,
+                };
+
+                [shade, shade, shade, 255]
+            })
+        })
+        .flatten()
+        .collect();
+
+    let image = images.add(Image::new(
+        Extent3d {
+            width: STAIRS_SIZE as u32,
+            height: STAIRS_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    commands.insert_resource(StairsTexture(image));
+}
+
+#[allow(clippy::too_many_arguments)]
+// stepping onto stairs moves a floor up or down, arriving on a floor never counts as stepping
+fn take_stairs_system(
+    size: Res<GridSize>,
+    map_gen_config: Res<MapGenConfig>,
+    mut game_rng: ResMut<GameRng>,
+    mut dungeon: ResMut<Dungeon>,
+    mut matrix: ResMut<Matrix<Node>>,
+    mut level: ResMut<Level>,
+    mut open_nodes: ResMut<OpenNodes>,
+    mut chunk_store: ResMut<ChunkStore>,
+    mut loaded_chunks: ResMut<LoadedChunks>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    mut history: ResMut<EditHistory>,
+    mut camera: ResMut<GameCamera>,
+    mut floor_changed_events: EventWriter<FloorChanged>,
+    mut p_query: Query<(&mut PlayerPosition, &mut LivePosition), With<Player>>,
+    mut last_cell: Local<Option<Coordinates>>,
+) {
+    if p_query.is_empty() {
+        return;
+    }
+
+    let (mut player_position, mut live_position) = p_query.single_mut();
+    let cell = player_position.current_position.0;
+
+    if *last_cell == Some(cell) {
+        return;
+    }
+
+    let stepped = last_cell.is_some();
+
+    *last_cell = Some(cell);
+
+    if !stepped {
+        return;
+    }
+
+    let floor = dungeon.floor;
+    let next_floor = if level.first_of(PlacementKind::Exit) == Some(cell) {
+        floor + 1
+    } else if floor > 1 && level.first_of(PlacementKind::Entrance) == Some(cell) {
+        floor - 1
+    } else {
+        return;
+    };
+
+    dirty_chunks.update(&chunk_store, &matrix, &level);
+    dirty_chunks.flush(&chunk_store, &matrix, &level);
+    unload_floor(&mut matrix, &mut level, &mut open_nodes);
+
+    // the cells are in the store, only the placements are kept in memory
+    dungeon.save(
+        floor,
+        SavedFloor {
+            matrix: matrix.clone(),
+            level: level.clone(),
+            open_nodes: Vec::new(),
+            visited: loaded_chunks.visited().clone(),
+        },
+    );
+
+    *chunk_store = ChunkStore::new(game_rng.seed, next_floor);
+
+    let (placements, visited) = match dungeon.take(next_floor) {
+        Some(saved) => (saved.level.placements, saved.visited),
+        None => {
+            let placements = open_floor(
+                &size,
+                &map_gen_config,
+                &mut game_rng,
+                &chunk_store,
+                |level| {
+                    if let Some(spawn) = level.first_of(PlacementKind::PlayerSpawn) {
+                        level.place(PlacementKind::Entrance, spawn);
+                    }
+                },
+            );
+
+            (placements, HashSet::new())
+        }
+    };
+
+    level.placements = placements;
+    *loaded_chunks = LoadedChunks::with_visited(visited);
+    dirty_chunks.skip(&matrix, &level);
+    history.clear();
+
+    // going down arrives at the stairs up and the other way around
+    let arrival = match next_floor > floor {
+        true => level.first_of(PlacementKind::Entrance),
+        false => level.first_of(PlacementKind::Exit),
+    }
+    .or_else(|| level.first_of(PlacementKind::PlayerSpawn))
+    .unwrap_or_default();
+
+    *player_position = PlayerPosition {
+        current_position: Position(arrival),
+        next_position: None,
+    };
+    *live_position = LivePosition((arrival.0 as f32, arrival.1 as f32));
+    camera.focus = live_position.0;
+    *last_cell = Some(arrival);
+
+    dungeon.floor = next_floor;
+    floor_changed_events.send(FloorChanged { floor: next_floor });
+
+    info!("entered floor {}", next_floor);
+}
+
+fn change_floor_system(
+    mut commands: Commands,
+    mut floor_changed_events: EventReader<FloorChanged>,
+    level: Res<Level>,
+    node_size: Res<NodeSize>,
+    stairs_texture: Res<StairsTexture>,
+    query: Query<Entity, With<Stairs>>,
+) {
+    if floor_changed_events.iter().last().is_none() {
+        return;
+    }
+
+    spawn_stairs(&mut commands, &level, &node_size, &stairs_texture, &query);
+}
+
+fn spawn_stairs_system(
+    mut commands: Commands,
+    level: Res<Level>,
+    node_size: Res<NodeSize>,
+    stairs_texture: Res<StairsTexture>,
+    query: Query<Entity, With<Stairs>>,
+) {
+    spawn_stairs(&mut commands, &level, &node_size, &stairs_texture, &query);
+}
+
+// the stairs of a floor only move when the floor changes or when they were edited
+fn spawn_stairs(
+    commands: &mut Commands,
+    level: &Level,
+    node_size: &NodeSize,
+    stairs_texture: &StairsTexture,
+    query: &Query<Entity, With<Stairs>>,
+) {
+    for entity in query {
+        commands.entity(entity).despawn();
+    }
+
+    for placement in &level.placements {
+        let flip_y = match placement.kind {
+            PlacementKind::Exit => false,
+            PlacementKind::Entrance => true,
+            _ => continue,
+        };
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(node_size.0 .0, node_size.0 .1)),
+                    flip_y,
+                    ..default()
+                },
+                texture: stairs_texture.0.clone(),
+                transform: Transform::from_xyz(0., 0., 99.5),
+                ..default()
+            },
+            Stairs(placement.coordinates),
+        ));
+    }
+}
+
+fn render_stairs_system(
+    node_size: Res<NodeSize>,
+    p_query: Query<&LivePosition>,
+    mut query: Query<(&Stairs, &mut Transform)>,
+) {
+    for live_position in &p_query {
+        for (stairs, mut transform) in &mut query {
+            let center =
+                node_size.cell_center(live_position, (stairs.0 .0 as f32, stairs.0 .1 as f32));
+
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::f32::consts::PI;
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    game::{dungeon::Dungeon, rng::GameRng},
+    GameMode, Health, Player,
+};
+
+// seconds the player takes to spin away before the screen shows
+const DYING_TIME: f32 = 1.5;
+const OVERLAY_ALPHA: f32 = 0.75;
+
+#[derive(Resource, Deref, DerefMut)]
+struct Dying(Timer);
+
+#[derive(Component)]
+struct GameOverOverlay;
+
+#[derive(Component)]
+struct GameOverText;
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(GameMode::Playing).with_system(death_system))
+            .add_system_set(SystemSet::on_enter(GameMode::GameOver).with_system(enter_system))
+            .add_system_set(
+                SystemSet::on_update(GameMode::GameOver)
+                    .with_system(dying_system)
+                    .with_system(quit_system),
+            );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn death_system(
+    mut state: ResMut<State<GameMode>>,
+    query: Query<&Health, (With<Player>, Changed<Health>)>,
+) {
+    if query.iter().any(|it| it.0 == 0) {
+        if let Err(error) = state.set(GameMode::GameOver) {
+            warn!("could not switch to {:?}: {:?}", GameMode::GameOver, error);
+        }
+    }
+}
+
+// the world stops, the death sequence runs on the real time
+fn enter_system(
+    mut commands: Commands,
+    mut time: ResMut<Time>,
+    asset_server: Res<AssetServer>,
+    dungeon: Res<Dungeon>,
+    game_rng: Res<GameRng>,
+) {
+    time.pause();
+    commands.insert_resource(Dying(Timer::from_seconds(DYING_TIME, TimerMode::Once)));
+
+    let font = asset_server.load("DejaVuSansMono.ttf");
+    let style = |font_size: f32| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    let mut text = TextBundle::from_sections([
+        TextSection::new("game over\n", style(48.)),
+        TextSection::new(
+            format!("floor {}, seed {}\n", dungeon.floor, game_rng.seed),
+            style(20.),
+        ),
+        TextSection::new("press enter to quit", style(16.)),
+    ])
+    .with_text_alignment(TextAlignment::CENTER);
+
+    text.visibility = Visibility::INVISIBLE;
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            GameOverOverlay,
+        ))
+        .with_children(|parent| {
+            parent.spawn((text, GameOverText));
+        });
+}
+
+#[allow(clippy::type_complexity)]
+// the player spins, shrinks and fades to red while the screen darkens
+fn dying_system(
+    time: Res<Time>,
+    mut dying: ResMut<Dying>,
+    mut p_query: Query<(&mut Transform, &mut TextureAtlasSprite), With<Player>>,
+    mut o_query: Query<&mut BackgroundColor, With<GameOverOverlay>>,
+    mut t_query: Query<&mut Visibility, With<GameOverText>>,
+    mut scale: Local<Option<Vec3>>,
+) {
+    if dying.finished() {
+        return;
+    }
+
+    dying.tick(time.raw_delta());
+
+    let t = dying.percent();
+
+    for (mut transform, mut sprite) in &mut p_query {
+        let full = *scale.get_or_insert(transform.scale);
+
+        transform.scale = full * (1. - t);
+        transform.rotation = Quat::from_rotation_z(t * 4. * PI);
+        sprite.color = Color::rgba(1., 1. - t, 1. - t, 1. - t);
+    }
+
+    for mut background in &mut o_query {
+        background.0 = Color::rgba(0., 0., 0., OVERLAY_ALPHA * t);
+    }
+
+    for mut visibility in &mut t_query {
+        visibility.is_visible = dying.finished();
+    }
+}
+
+fn quit_system(
+    keys: Res<Input<KeyCode>>,
+    dying: Res<Dying>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    if dying.finished() && keys.any_just_pressed([KeyCode::Return, KeyCode::Escape]) {
+        exit_events.send(AppExit);
+    }
+}
@@ -1,34 +1,393 @@
-use bevy::prelude::*;
-use mapgen::{
-    AreaStartingPosition, CellularAutomata, CullUnreachable, DistantExit, MapBuilder,
-    NoiseGenerator, XStart, YStart,
-};
+use std::collections::{HashMap, HashSet};
+
+use bevy::{app::AppExit, prelude::*};
 use rand::prelude::*;
 
 use crate::{
-    game::{coordinates::Coordinates, matrix::Matrix, node::Entry},
-    game::{movement::Movement, node::Node},
-    GridSize, LivePosition, NodeSize, Position, UserCursorPressedState, UserPosition,
+    game::node::Node,
+    game::{
+        chunk::{all_chunks, chunk_of, chunk_origin, ChunkCoordinates, CHUNK_SIZE},
+        coordinates::Coordinates,
+        door::place_barriers,
+        encoded_matrix::EncodedMatrix,
+        hazard::{place_hazards, Hazard},
+        level::{Decoration, Level, Placement, PlacementKind, FLOOR_VARIANTS},
+        map_gen::MapGenConfig,
+        matrix::Matrix,
+        rng::GameRng,
+    },
+    GameMode, GridSize, LivePosition, NodeSize, Player, PlayerPosition, UserCursorPressedState,
+    UserPosition,
 };
 
-use super::assets::GridTextures;
+use super::camera::GameCamera;
 
 #[derive(Resource)]
 pub struct OpenNodes(pub Vec<Coordinates>);
 
+#[derive(Resource, Default)]
+pub struct LoadedChunks {
+    center: Option<ChunkCoordinates>,
+    radius: (usize, usize),
+    player: Option<ChunkCoordinates>,
+    chunks: HashSet<ChunkCoordinates>,
+    visited: HashSet<ChunkCoordinates>,
+}
+
+impl LoadedChunks {
+    pub fn chunks(&self) -> impl Iterator<Item = &ChunkCoordinates> {
+        self.chunks.iter()
+    }
+
+    pub fn contains(&self, chunk: &ChunkCoordinates) -> bool {
+        self.chunks.contains(chunk)
+    }
+
+    pub(crate) fn visited(&self) -> &HashSet<ChunkCoordinates> {
+        &self.visited
+    }
+
+    pub(crate) fn with_visited(visited: HashSet<ChunkCoordinates>) -> Self {
+        Self {
+            visited,
+            ..default()
+        }
+    }
+}
+
+// chunks that changed since they were loaded, in the cells of any layer or in their placements
+#[derive(Resource, Default)]
+pub struct DirtyChunks {
+    chunks: HashSet<ChunkCoordinates>,
+    generations: Option<[u64; 5]>,
+    placements: Vec<Placement>,
+}
+
+impl DirtyChunks {
+    fn generations(matrix: &Matrix<Node>, level: &Level) -> [u64; 5] {
+        [
+            matrix.changes.generation(),
+            level.floor.changes.generation(),
+            level.decoration.changes.generation(),
+            level.wall_damage.changes.generation(),
+            level.hazard.changes.generation(),
+        ]
+    }
+
+    // placements of chunks that are not loaded go to their files right away,
+    // there is nothing else of those chunks in memory that could be saved with them later
+    pub(crate) fn update(
+        &mut self,
+        chunk_store: &ChunkStore,
+        matrix: &Matrix<Node>,
+        level: &Level,
+    ) {
+        let generations = Self::generations(matrix, level);
+        let last_generations = match self.generations.replace(generations) {
+            Some(value) => value,
+            None => {
+                self.placements = level.placements.clone();
+
+                return;
+            }
+        };
+
+        if generations != last_generations {
+            let logs = [
+                &matrix.changes,
+                &level.floor.changes,
+                &level.decoration.changes,
+                &level.wall_damage.changes,
+                &level.hazard.changes,
+            ];
+
+            for (log, generation) in logs.into_iter().zip(last_generations) {
+                match log.since(generation) {
+                    Some(cells) => self.chunks.extend(cells.iter().map(chunk_of)),
+                    None => self.chunks.extend(
+                        all_chunks(matrix.chunk_count()).filter(|it| matrix.is_chunk_loaded(it)),
+                    ),
+                }
+            }
+        }
+
+        if level.placements == self.placements {
+            return;
+        }
+
+        // placements that were added or removed, whatever the order they are kept in
+        let mut counts: HashMap<(u8, Coordinates), i32> = HashMap::new();
+
+        for placement in &level.placements {
+            *counts
+                .entry((placement.kind.into(), placement.coordinates))
+                .or_default() += 1;
+        }
+
+        for placement in &self.placements {
+            *counts
+                .entry((placement.kind.into(), placement.coordinates))
+                .or_default() -= 1;
+        }
+
+        let changed: HashSet<ChunkCoordinates> = counts
+            .into_iter()
+            .filter(|it| it.1 != 0)
+            .map(|((_, coordinates), _)| chunk_of(&coordinates))
+            .collect();
+
+        self.placements = level.placements.clone();
+
+        for chunk in changed {
+            if matrix.is_chunk_loaded(&chunk) {
+                self.chunks.insert(chunk);
+            } else if let Err(error) = chunk_store.save_placements(&chunk, level) {
+                warn!("could not save placements of chunk {:?}: {}", chunk, error);
+            }
+        }
+    }
+
+    // forgets whatever changed since the last update, for writes that bring back what is on disk
+    pub(crate) fn skip(&mut self, matrix: &Matrix<Node>, level: &Level) {
+        self.generations = Some(Self::generations(matrix, level));
+        self.placements = level.placements.clone();
+    }
+
+    fn save(
+        &mut self,
+        chunk: &ChunkCoordinates,
+        chunk_store: &ChunkStore,
+        matrix: &Matrix<Node>,
+        level: &Level,
+    ) {
+        if !self.chunks.remove(chunk) {
+            return;
+        }
+
+        if let Err(error) = chunk_store.save(chunk, matrix, level) {
+            warn!("could not save chunk {:?}: {}", chunk, error);
+        }
+    }
+
+    pub(crate) fn flush(&mut self, chunk_store: &ChunkStore, matrix: &Matrix<Node>, level: &Level) {
+        let chunks: Vec<ChunkCoordinates> = self.chunks.iter().copied().collect();
+
+        for chunk in chunks {
+            self.save(&chunk, chunk_store, matrix, level);
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct ChunkStore {
+    directory: String,
+}
+
+impl ChunkStore {
+    // the first floor keeps the directory it had before there were more floors
+    pub(crate) fn new(seed: u64, floor: usize) -> Self {
+        let directory = match floor {
+            1 => format!("chunks/{seed:016x}"),
+            _ => format!("chunks/{seed:016x}/floor_{floor}"),
+        };
+
+        Self { directory }
+    }
+
+    fn file_name(&self, chunk: &ChunkCoordinates) -> String {
+        format!("{}/{}_{}.chunk", self.directory, chunk.0, chunk.1)
+    }
+
+    fn contains(&self, chunk: &ChunkCoordinates) -> bool {
+        std::path::Path::new(&self.file_name(chunk)).exists()
+    }
+
+    fn load_encoded(&self, chunk: &ChunkCoordinates) -> Option<EncodedMatrix> {
+        if !self.contains(chunk) {
+            return None;
+        }
+
+        match EncodedMatrix::from_file(&self.file_name(chunk)) {
+            Ok(encoded) => Some(encoded),
+            Err(error) => {
+                warn!("could not load chunk {:?}: {}", chunk, error);
+
+                None
+            }
+        }
+    }
+
+    // the placements of the chunk come relative to its origin
+    fn load(&self, chunk: &ChunkCoordinates) -> Option<(Matrix<Node>, Level)> {
+        self.load_encoded(chunk).map(Into::into)
+    }
+
+    pub(crate) fn save(
+        &self,
+        chunk: &ChunkCoordinates,
+        matrix: &Matrix<Node>,
+        level: &Level,
+    ) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+
+        EncodedMatrix::from((&matrix.chunk(chunk), &level.chunk(chunk)))
+            .to_file(&self.file_name(chunk))
+    }
+
+    // for chunks that are not loaded, the chunk is edited right in its file
+    pub(crate) fn edit(
+        &self,
+        chunk: &ChunkCoordinates,
+        edit: impl FnOnce(&mut Matrix<Node>, &mut Level),
+    ) -> std::io::Result<()> {
+        let file_name = self.file_name(chunk);
+        let (mut matrix, mut level): (Matrix<Node>, Level) =
+            EncodedMatrix::from_file(&file_name)?.into();
+
+        edit(&mut matrix, &mut level);
+        EncodedMatrix::from((&matrix, &level)).to_file(&file_name)
+    }
+
+    // the cells of the chunk stay as they were saved
+    fn save_placements(&self, chunk: &ChunkCoordinates, level: &Level) -> std::io::Result<()> {
+        self.edit(chunk, |_, chunk_level| {
+            chunk_level.placements = level.chunk_placements(chunk)
+        })
+    }
+
+    pub(crate) fn save_all(&self, matrix: &Matrix<Node>, level: &Level) -> std::io::Result<()> {
+        all_chunks(matrix.chunk_count()).try_for_each(|it| self.save(&it, matrix, level))
+    }
+
+    // the placements of every chunk on disk, for the whole floor
+    fn placements(&self, chunk_count: (usize, usize)) -> Vec<Placement> {
+        all_chunks(chunk_count)
+            .flat_map(|chunk| {
+                let origin = chunk_origin(&chunk);
+
+                self.load_encoded(&chunk)
+                    .map(|it| it.placements)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(move |it| Placement {
+                        kind: it.kind,
+                        coordinates: (it.coordinates.0 + origin.0, it.coordinates.1 + origin.1),
+                    })
+            })
+            .collect()
+    }
+
+    // every chunk on disk in one matrix, with the floor and decorations but no placements
+    pub(crate) fn whole_floor(&self, rows: usize, cols: usize) -> (Matrix<Node>, Level) {
+        let mut matrix = Matrix::new(rows, cols, Node::closed());
+        let mut level = Level::new(rows, cols);
+
+        for chunk in all_chunks(matrix.chunk_count()) {
+            if let Some((chunk_matrix, chunk_level)) = self.load(&chunk) {
+                matrix.paste_chunk(&chunk, &chunk_matrix);
+                level.paste_chunk(&chunk, &chunk_level);
+            }
+        }
+
+        (matrix, level)
+    }
+}
+
+// a floor is generated whole the first time it is entered and goes to its store chunk by chunk,
+// from then on it only comes back from the store, chunks already on disk win over generated ones
+pub(crate) fn open_floor(
+    size: &GridSize,
+    map_gen_config: &MapGenConfig,
+    game_rng: &mut GameRng,
+    chunk_store: &ChunkStore,
+    prepare: impl FnOnce(&mut Level),
+) -> Vec<Placement> {
+    let chunk_count = (
+        size.0 .0.div_ceil(CHUNK_SIZE),
+        size.0 .1.div_ceil(CHUNK_SIZE),
+    );
+
+    if !all_chunks(chunk_count).all(|it| chunk_store.contains(&it)) {
+        let mut m = Matrix::new(size.0 .0, size.0 .1, Node::open());
+        let (_, mut level) = prepare_grid(size, map_gen_config, &mut m, game_rng);
+
+        prepare(&mut level);
+
+        for chunk in all_chunks(chunk_count).filter(|it| !chunk_store.contains(it)) {
+            if let Err(error) = chunk_store.save(&chunk, &m, &level) {
+                warn!("could not save chunk {:?}: {}", chunk, error);
+            }
+        }
+    }
+
+    chunk_store.placements(chunk_count)
+}
+
+// every chunk is dropped without being saved and every consumer refreshes,
+// the chunks that are needed come back from the store with the next frame
+pub(crate) fn unload_floor(
+    matrix: &mut Matrix<Node>,
+    level: &mut Level,
+    open_nodes: &mut OpenNodes,
+) {
+    for chunk in all_chunks(matrix.chunk_count()) {
+        matrix.unload_chunk(&chunk);
+        level.unload_chunk(&chunk);
+    }
+
+    matrix.changes.invalidate();
+    level.floor.changes.invalidate();
+    level.decoration.changes.invalidate();
+    level.wall_damage.changes.invalidate();
+    level.hazard.changes.invalidate();
+    open_nodes.0.clear();
+}
+
+fn load_chunk(
+    chunk: &ChunkCoordinates,
+    chunk_store: &ChunkStore,
+    matrix: &mut Matrix<Node>,
+    level: &mut Level,
+    open_nodes: &mut OpenNodes,
+) -> bool {
+    let (chunk_matrix, chunk_level) = match chunk_store.load(chunk) {
+        Some(value) => value,
+        None => {
+            warn!("chunk {:?} is missing from the store", chunk);
+
+            return false;
+        }
+    };
+
+    matrix.paste_chunk(chunk, &chunk_matrix);
+    level.paste_chunk(chunk, &chunk_level);
+
+    // nothing should start on a hazard, least of all on a pit or a teleporter
+    open_nodes.0.extend(
+        matrix
+            .chunk_cells(chunk)
+            .filter(|it| !matrix[*it].is_wall() && level.hazard[*it] == Hazard::None),
+    );
+
+    true
+}
+
 pub struct GridPlugin;
 
 impl Plugin for GridPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup_system)
-            .add_system(layout_grid_system)
-            .add_system(render_grid_system.after(layout_grid_system))
-            .add_system(render_user_position_system.after(layout_grid_system))
+        app.init_resource::<MapGenConfig>()
+            .add_state(GameMode::Playing)
+            .add_startup_system(setup_system)
+            .add_system(stream_chunks_system)
+            .add_system(render_user_position_system)
             .add_system(update_user_position_coordinates_system)
             .add_system(update_user_position_cursor_pressed_system)
-            .add_system(
+            .add_system_set(SystemSet::on_update(GameMode::Playing).with_system(
                 modify_single_node_system.after(update_user_position_cursor_pressed_system),
-            );
+            ))
+            .add_system(mark_dirty_chunks_system.after(modify_single_node_system))
+            .add_system_to_stage(CoreStage::Last, save_on_exit_system);
     }
 
     fn name(&self) -> &str {
@@ -36,13 +395,25 @@ impl Plugin for GridPlugin {
     }
 }
 
-fn setup_system(mut commands: Commands, size: Res<GridSize>, node_size: Res<NodeSize>) {
+fn setup_system(
+    mut commands: Commands,
+    size: Res<GridSize>,
+    node_size: Res<NodeSize>,
+    map_gen_config: Res<MapGenConfig>,
+    mut game_rng: ResMut<GameRng>,
+) {
     let rows = size.0 .0;
     let cols = size.0 .1;
-    let mut m = Matrix::new(rows, cols, Node::open());
-    let open_nodes = prepare_grid(&size, &mut m);
+    let chunk_store = ChunkStore::new(game_rng.seed, 1);
+    let mut level = Level::unloaded(rows, cols);
+
+    level.placements = open_floor(&size, &map_gen_config, &mut game_rng, &chunk_store, |_| {});
 
-    commands.insert_resource(OpenNodes(open_nodes));
+    commands.insert_resource(OpenNodes(Vec::new()));
+    commands.insert_resource(level);
+    commands.insert_resource(chunk_store);
+    commands.insert_resource(LoadedChunks::default());
+    commands.insert_resource(DirtyChunks::default());
 
     commands
         .spawn(UserPosition {
@@ -59,135 +430,150 @@ fn setup_system(mut commands: Commands, size: Res<GridSize>, node_size: Res<Node
             ..default()
         });
 
-    let cols = size.0 .1;
-
-    m.vec.iter().enumerate().for_each(|(index, node)| {
-        let row = index / cols;
-        let col = index % cols;
-
-        commands
-            .spawn_empty()
-            .insert(*node)
-            .insert(Position((row, col)))
-            .insert(SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(node_size.0 .0, node_size.0 .1)),
-                    ..default()
-                },
-                transform: Transform {
-                    translation: Vec3 {
-                        x: 0.,
-                        y: 0.,
-                        z: 99.,
-                    },
-                    ..default()
-                },
-                ..default()
-            });
-    });
-
-    commands.insert_resource(m);
+    commands.insert_resource(Matrix::unloaded(rows, cols, Node::closed()));
 }
 
-fn layout_grid_system(
+#[allow(clippy::too_many_arguments)]
+// chunks are streamed around the camera focus, so that panning away from the player works too,
+// the chunks right around the player stay loaded either way
+fn stream_chunks_system(
+    windows: Res<Windows>,
     node_size: Res<NodeSize>,
-    mut query: Query<(&Position, &mut Transform)>,
-    p_query: Query<&LivePosition, Changed<LivePosition>>,
+    camera: Res<GameCamera>,
+    chunk_store: Res<ChunkStore>,
+    mut matrix: ResMut<Matrix<Node>>,
+    mut level: ResMut<Level>,
+    mut open_nodes: ResMut<OpenNodes>,
+    mut loaded_chunks: ResMut<LoadedChunks>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    p_query: Query<&PlayerPosition, With<Player>>,
 ) {
-    if p_query.is_empty() {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let center = chunk_of(&(
+        camera.focus.0.max(0.).round() as usize,
+        camera.focus.1.max(0.).round() as usize,
+    ));
+    let player = p_query
+        .get_single()
+        .ok()
+        .map(|it| chunk_of(&it.current_position.0));
+    let r_rows = (window.height() * camera.zoom / 2. / node_size.0 .1 / CHUNK_SIZE as f32).ceil()
+        as usize
+        + 1;
+    let r_cols = (window.width() * camera.zoom / 2. / node_size.0 .0 / CHUNK_SIZE as f32).ceil()
+        as usize
+        + 1;
+
+    if loaded_chunks.center == Some(center)
+        && loaded_chunks.radius == (r_rows, r_cols)
+        && loaded_chunks.player == player
+    {
         return;
     }
 
-    let live_position = p_query.single();
-
-    for (position, mut transform) in &mut query {
-        transform.translation.x =
-            (position.0 .1 as f32 - live_position.0 .1) * node_size.0 .0 + node_size.0 .0 / 2.;
-        transform.translation.y =
-            (live_position.0 .0 - position.0 .0 as f32) * node_size.0 .1 - node_size.0 .1 / 2.;
+    loaded_chunks.center = Some(center);
+    loaded_chunks.radius = (r_rows, r_cols);
+    loaded_chunks.player = player;
+
+    let in_range = |chunk: &ChunkCoordinates, margin: usize| {
+        (chunk.0.abs_diff(center.0) <= r_rows + margin
+            && chunk.1.abs_diff(center.1) <= r_cols + margin)
+            || player.is_some_and(|it| {
+                chunk.0.abs_diff(it.0) <= 1 + margin && chunk.1.abs_diff(it.1) <= 1 + margin
+            })
+    };
+
+    // edits made since the last frame have to be known before their chunks go
+    dirty_chunks.update(&chunk_store, &matrix, &level);
+
+    let unload: Vec<ChunkCoordinates> = loaded_chunks
+        .chunks
+        .iter()
+        .filter(|it| !in_range(it, 1))
+        .copied()
+        .collect();
+
+    for chunk in unload {
+        dirty_chunks.save(&chunk, &chunk_store, &matrix, &level);
+        matrix.unload_chunk(&chunk);
+        level.unload_chunk(&chunk);
+        open_nodes.0.retain(|it| chunk_of(it) != chunk);
+        loaded_chunks.chunks.remove(&chunk);
     }
-}
 
-fn render_grid_system(
-    grid_textures: Res<GridTextures>,
-    matrix: Res<Matrix<Node>>,
-    mut query: Query<(&Node, &Position, &mut Handle<Image>), Changed<Node>>,
-) {
-    for (node, position, mut handle) in &mut query {
-        *handle = match node[Entry::LEFT] {
-            true => grid_textures.random_floor_tile(&position),
-            false => {
-                let left = matrix.left(&position.0);
-                let top = matrix.up(&position.0);
-                let right = matrix.right(&position.0);
-                let bottom = matrix.down(&position.0);
-
-                grid_textures.resolve_wall_tile(&left, &top, &right, &bottom)
-            }
-        };
+    let load: Vec<ChunkCoordinates> = all_chunks(matrix.chunk_count())
+        .filter(|it| in_range(it, 0) && !loaded_chunks.chunks.contains(it))
+        .collect();
+
+    for chunk in load {
+        if load_chunk(
+            &chunk,
+            &chunk_store,
+            &mut matrix,
+            &mut level,
+            &mut open_nodes,
+        ) {
+            loaded_chunks.chunks.insert(chunk);
+            loaded_chunks.visited.insert(chunk);
+        }
     }
+
+    // loading only brings back what is on disk already
+    dirty_chunks.skip(&matrix, &level);
 }
 
 fn render_user_position_system(
     node_size: Res<NodeSize>,
     mut pos_query: Query<(&UserPosition, &mut Transform), Changed<UserPosition>>,
-    query: Query<&Position, With<Node>>,
     p_query: Query<&LivePosition>,
 ) {
     for (user_position, mut transform) in &mut pos_query {
-        for position in &query {
-            if user_position.coordinates == Some(position.0) {
-                for live_position in &p_query {
-                    transform.translation.x = (position.0 .1 as f32 - live_position.0 .1)
-                        * node_size.0 .0
-                        + node_size.0 .0 / 2.;
-                    transform.translation.y = (live_position.0 .0 - position.0 .0 as f32)
-                        * node_size.0 .1
-                        - node_size.0 .1 / 2.;
-                    transform.translation.z = 100.;
-                }
+        if let Some(coordinates) = user_position.coordinates {
+            for live_position in &p_query {
+                let center = node_size
+                    .cell_center(live_position, (coordinates.0 as f32, coordinates.1 as f32));
+
+                transform.translation.x = center.x;
+                transform.translation.y = center.y;
+                transform.translation.z = 100.;
             }
         }
     }
 }
 
+// the cell under the cursor follows from the inverse of the grid layout and the camera,
+// renderers don't need to spawn an entity per cell for picking to work
 fn update_user_position_coordinates_system(
     windows: Res<Windows>,
     node_size: Res<NodeSize>,
+    camera: Res<GameCamera>,
     matrix: Res<Matrix<Node>>,
+    p_query: Query<&LivePosition>,
     mut query: Query<&mut UserPosition>,
-    n_query: Query<(&Position, &Transform), With<Node>>,
 ) {
-    if let Some(window) = windows.get_primary() {
-        let w = window.width() / 2.;
-        let h = window.height() / 2.;
-
-        if let Some(pos) = window.cursor_position() {
-            for (position, transform) in &n_query {
-                if pos.x - w >= transform.translation.x - node_size.0 .0 / 2.
-                    && pos.x - w < transform.translation.x + node_size.0 .1 - node_size.0 .0 / 2.
-                    && pos.y - h >= transform.translation.y - node_size.0 .1 / 2.
-                    && pos.y - h < transform.translation.y + node_size.0 .0 - node_size.0 .1 / 2.
-                {
-                    for mut user_position in &mut query {
-                        let mut val = user_position.coordinates;
-
-                        if matrix.contains(&position.0) {
-                            val = Some(position.0);
-                        }
-
-                        if user_position.coordinates != val {
-                            *user_position = UserPosition {
-                                coordinates: val,
-                                cursor_pressed_state: user_position.cursor_pressed_state,
-                                target_modification: user_position.target_modification,
-                            };
-                        }
-
-                        return;
-                    }
-                }
-            }
+    if p_query.is_empty() {
+        return;
+    }
+
+    let val = windows
+        .get_primary()
+        .and_then(|window| camera.cursor_cell(window, &node_size, p_query.single()))
+        .filter(|it| matrix.contains(it));
+
+    if val.is_none() {
+        return;
+    }
+
+    for mut user_position in &mut query {
+        if user_position.coordinates != val {
+            *user_position = UserPosition {
+                coordinates: val,
+                cursor_pressed_state: user_position.cursor_pressed_state,
+                target_modification: user_position.target_modification,
+            };
         }
     }
 }
@@ -220,7 +606,6 @@ fn update_user_position_cursor_pressed_system(
 }
 
 fn modify_single_node_system(
-    mut lookup_query: Query<(&mut Node, &Position)>,
     mut query: Query<&mut UserPosition, Changed<UserPosition>>,
     mut matrix: ResMut<Matrix<Node>>,
 ) {
@@ -234,29 +619,13 @@ fn modify_single_node_system(
                 let target_modification =
                     user_position
                         .target_modification
-                        .unwrap_or(if matrix[coordinates].left {
-                            Node::closed()
-                        } else {
+                        .unwrap_or(if matrix[coordinates].is_wall() {
                             Node::open()
+                        } else {
+                            Node::closed()
                         });
 
-                matrix[coordinates] = target_modification;
-
-                for (mut node, position) in &mut lookup_query {
-                    if position.0 == coordinates {
-                        *node = matrix[coordinates];
-                    } else if coordinates.0 > 0 && position.0 == (coordinates.0 - 1, coordinates.1)
-                    {
-                        *node = node.clone();
-                    } else if position.0 == (coordinates.0 + 1, coordinates.1) {
-                        *node = node.clone();
-                    } else if coordinates.1 > 0 && position.0 == (coordinates.0, coordinates.1 - 1)
-                    {
-                        *node = node.clone();
-                    } else if position.0 == (coordinates.0, coordinates.1 + 1) {
-                        *node = node.clone();
-                    }
-                }
+                matrix.set(coordinates, target_modification);
 
                 if user_position.target_modification != Some(target_modification) {
                     *user_position = UserPosition {
@@ -270,20 +639,43 @@ fn modify_single_node_system(
     }
 }
 
+fn mark_dirty_chunks_system(
+    chunk_store: Res<ChunkStore>,
+    matrix: Res<Matrix<Node>>,
+    level: Res<Level>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+) {
+    dirty_chunks.update(&chunk_store, &matrix, &level);
+}
+
+// chunks that are still loaded would lose their changes when the app closes
+fn save_on_exit_system(
+    exit_events: EventReader<AppExit>,
+    chunk_store: Res<ChunkStore>,
+    matrix: Res<Matrix<Node>>,
+    level: Res<Level>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+) {
+    if exit_events.is_empty() {
+        return;
+    }
+
+    dirty_chunks.update(&chunk_store, &matrix, &level);
+    dirty_chunks.flush(&chunk_store, &matrix, &level);
+}
+
 // see https://github.com/klangner/mapgen.rs/blob/master/demo/src/lib.rs
-fn prepare_grid(size: &Res<GridSize>, m: &mut Matrix<Node>) -> Vec<Coordinates> {
-    let mut t_rng = rand::thread_rng();
-    let seed = t_rng.gen();
-    let mut rng = StdRng::seed_from_u64(seed);
+pub(crate) fn prepare_grid(
+    size: &GridSize,
+    map_gen_config: &MapGenConfig,
+    m: &mut Matrix<Node>,
+    game_rng: &mut GameRng,
+) -> (Vec<Coordinates>, Level) {
+    let rng = &mut game_rng.map;
     let rows = size.0 .0;
     let cols = size.0 .1;
-    let map = MapBuilder::new(rows, cols)
-        .with(NoiseGenerator::uniform())
-        .with(CellularAutomata::new())
-        .with(AreaStartingPosition::new(XStart::LEFT, YStart::BOTTOM))
-        .with(CullUnreachable::new())
-        .with(DistantExit::new())
-        .build_with_rng(&mut rng);
+    let map = map_gen_config.build(rows, cols, rng);
+    let mut level = Level::new(rows, cols);
     let mut open_nodes = Vec::new();
     let mut row = 0;
     let mut col = 0;
@@ -291,9 +683,15 @@ fn prepare_grid(size: &Res<GridSize>, m: &mut Matrix<Node>) -> Vec<Coordinates>
     map.tiles.into_iter().for_each(|it| {
         let coordinates = (row, col);
 
+        level.floor[coordinates] = rng.gen_range(0..FLOOR_VARIANTS);
+
         if it.is_blocked() {
             m[coordinates] = Node::closed();
         } else {
+            if rng.gen::<f32>() < 0.03 {
+                level.decoration[coordinates] = Decoration::from(rng.gen_range(1..4));
+            }
+
             open_nodes.push(coordinates);
         }
 
@@ -305,5 +703,32 @@ fn prepare_grid(size: &Res<GridSize>, m: &mut Matrix<Node>) -> Vec<Coordinates>
         }
     });
 
-    open_nodes
+    if let Some(point) = map.starting_point {
+        level.place(PlacementKind::PlayerSpawn, (point.y, point.x));
+    }
+
+    if let Some(point) = map.exit_point {
+        level.place(PlacementKind::Exit, (point.y, point.x));
+    }
+
+    place_barriers(m, &mut level, &mut game_rng.map);
+
+    let rng = &mut game_rng.loot;
+
+    (0..100).for_each(|_| {
+        let coordinates = open_nodes[rng.gen_range(0..open_nodes.len())];
+        let kind = match rng.gen_range(0..2) {
+            0 => PlacementKind::ProjectilePowerUp,
+            _ => PlacementKind::SpeedPowerUp,
+        };
+
+        level.place(kind, coordinates);
+    });
+
+    place_hazards(m, &mut level, &mut game_rng.map);
+
+    // nothing should start on a hazard, least of all on a pit or a teleporter
+    open_nodes.retain(|it| level.hazard[*it] == Hazard::None);
+
+    (open_nodes, level)
 }
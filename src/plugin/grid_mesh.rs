@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    asset::LoadState,
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use crate::{
+    game::{
+        autotile::affected_cells,
+        chunk::{chunk_of, chunk_origin, ChunkCoordinates},
+        level::Level,
+        matrix::Matrix,
+        node::Node,
+    },
+    LivePosition, NodeSize,
+};
+
+use super::{assets::GridTextures, grid::LoadedChunks};
+
+#[derive(Component)]
+pub struct ChunkMesh(pub ChunkCoordinates);
+
+// the grid textures packed into a single image, so that a chunk is one draw call
+#[derive(Resource)]
+pub struct GridAtlas {
+    atlas: TextureAtlas,
+    material: Handle<ColorMaterial>,
+}
+
+impl GridAtlas {
+    // (min, max) uv of a grid texture, or of a region within it
+    fn uv(&self, handle: &Handle<Image>, region: Option<Rect>) -> (Vec2, Vec2) {
+        let rect = self.atlas.textures[self.atlas.get_texture_index(handle).unwrap_or(0)];
+        let (min, max) = match region {
+            Some(region) => (rect.min + region.min, rect.min + region.max),
+            None => (rect.min, rect.max),
+        };
+
+        (min / self.atlas.size, max / self.atlas.size)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ChunkMeshes(HashMap<ChunkCoordinates, Entity>);
+
+pub struct GridMeshPlugin;
+
+impl Plugin for GridMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkMeshes>()
+            .add_system(build_atlas_system)
+            .add_system(sync_chunk_meshes_system.after(build_atlas_system))
+            .add_system(rebuild_chunk_meshes_system.after(sync_chunk_meshes_system))
+            .add_system(layout_chunk_meshes_system.after(sync_chunk_meshes_system));
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn build_atlas_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    grid_textures: Option<Res<GridTextures>>,
+    grid_atlas: Option<Res<GridAtlas>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let grid_textures = match (grid_textures, grid_atlas) {
+        (Some(grid_textures), None) => grid_textures,
+        _ => return,
+    };
+    let handles = grid_textures.handles();
+
+    if asset_server.get_group_load_state(handles.iter().map(|it| it.id())) != LoadState::Loaded {
+        return;
+    }
+
+    let mut builder = TextureAtlasBuilder::default();
+
+    for handle in handles {
+        if let Some(image) = images.get(&handle) {
+            builder.add_texture(handle, image);
+        }
+    }
+
+    match builder.finish(&mut images) {
+        Ok(atlas) => {
+            let material = materials.add(ColorMaterial::from(atlas.texture.clone()));
+
+            commands.insert_resource(GridAtlas { atlas, material });
+        }
+        Err(error) => error!("could not pack the grid textures: {:?}", error),
+    }
+}
+
+// keeps one mesh per loaded chunk
+#[allow(clippy::too_many_arguments)]
+fn sync_chunk_meshes_system(
+    mut commands: Commands,
+    grid_textures: Res<GridTextures>,
+    grid_atlas: Option<Res<GridAtlas>>,
+    node_size: Res<NodeSize>,
+    matrix: Res<Matrix<Node>>,
+    level: Res<Level>,
+    loaded_chunks: Res<LoadedChunks>,
+    mut chunk_meshes: ResMut<ChunkMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let grid_atlas = match grid_atlas {
+        Some(grid_atlas) => grid_atlas,
+        None => return,
+    };
+
+    chunk_meshes.0.retain(|chunk, entity| {
+        let keep = loaded_chunks.contains(chunk);
+
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+
+        keep
+    });
+
+    for chunk in loaded_chunks.chunks() {
+        if chunk_meshes.0.contains_key(chunk) {
+            continue;
+        }
+
+        let mesh = build_chunk_mesh(
+            chunk,
+            &grid_textures,
+            &grid_atlas,
+            &node_size,
+            &matrix,
+            &level,
+        );
+        let entity = commands
+            .spawn(MaterialMesh2dBundle {
+                mesh: meshes.add(mesh).into(),
+                material: grid_atlas.material.clone(),
+                transform: Transform::from_xyz(0., 0., 99.),
+                ..default()
+            })
+            .insert(ChunkMesh(*chunk))
+            .id();
+
+        chunk_meshes.0.insert(*chunk, entity);
+    }
+}
+
+// a chunk is rebuilt when a cell in it, or a cell bordering it, was edited
+#[allow(clippy::too_many_arguments)]
+fn rebuild_chunk_meshes_system(
+    grid_textures: Res<GridTextures>,
+    grid_atlas: Option<Res<GridAtlas>>,
+    node_size: Res<NodeSize>,
+    matrix: Res<Matrix<Node>>,
+    level: Res<Level>,
+    mut generation: Local<Option<u64>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    query: Query<(&ChunkMesh, &Mesh2dHandle)>,
+) {
+    let grid_atlas = match grid_atlas {
+        Some(grid_atlas) => grid_atlas,
+        None => return,
+    };
+    let last_generation = match *generation {
+        Some(value) => value,
+        None => {
+            *generation = Some(matrix.changes.generation());
+
+            return;
+        }
+    };
+
+    if matrix.changes.generation() == last_generation {
+        return;
+    }
+
+    *generation = Some(matrix.changes.generation());
+
+    let chunks: Option<HashSet<ChunkCoordinates>> =
+        matrix.changes.since(last_generation).map(|cells| {
+            cells
+                .iter()
+                .flat_map(affected_cells)
+                .map(|it| chunk_of(&it))
+                .collect()
+        });
+
+    for (chunk_mesh, handle) in &query {
+        if chunks.as_ref().is_none_or(|it| it.contains(&chunk_mesh.0)) {
+            if let Some(mesh) = meshes.get_mut(&handle.0) {
+                *mesh = build_chunk_mesh(
+                    &chunk_mesh.0,
+                    &grid_textures,
+                    &grid_atlas,
+                    &node_size,
+                    &matrix,
+                    &level,
+                );
+            }
+        }
+    }
+}
+
+fn layout_chunk_meshes_system(
+    node_size: Res<NodeSize>,
+    mut query: Query<(&ChunkMesh, &mut Transform)>,
+    p_query: Query<&LivePosition>,
+) {
+    if p_query.is_empty() {
+        return;
+    }
+
+    let live_position = p_query.single();
+
+    for (chunk_mesh, mut transform) in &mut query {
+        let origin = chunk_origin(&chunk_mesh.0);
+
+        let corner = node_size.cell_corner(live_position, (origin.0 as f32, origin.1 as f32));
+
+        transform.translation.x = corner.x;
+        transform.translation.y = corner.y;
+    }
+}
+
+// one quad per cell, relative to the top left corner of the chunk
+fn build_chunk_mesh(
+    chunk: &ChunkCoordinates,
+    grid_textures: &GridTextures,
+    grid_atlas: &GridAtlas,
+    node_size: &NodeSize,
+    matrix: &Matrix<Node>,
+    level: &Level,
+) -> Mesh {
+    let origin = chunk_origin(chunk);
+    let (w, h) = node_size.0;
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut colors: Vec<[f32; 4]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    for coordinates in matrix.chunk_cells(chunk) {
+        let (handle, region, tint) = grid_textures.resolve_cell(matrix, level, &coordinates);
+        let (min, max) = grid_atlas.uv(&handle, region);
+        let x = (coordinates.1 - origin.1) as f32 * w;
+        let y = -((coordinates.0 - origin.0) as f32) * h;
+        let offset = positions.len() as u32;
+
+        positions.extend([
+            [x, y - h, 0.],
+            [x + w, y - h, 0.],
+            [x + w, y, 0.],
+            [x, y, 0.],
+        ]);
+        uvs.extend([
+            [min.x, max.y],
+            [max.x, max.y],
+            [max.x, min.y],
+            [min.x, min.y],
+        ]);
+        colors.extend([tint.as_linear_rgba_f32(); 4]);
+        indices.extend([0, 1, 2, 0, 2, 3].map(|it| offset + it));
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+
+    mesh
+}
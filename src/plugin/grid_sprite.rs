@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+
+use crate::{
+    game::{autotile::affected_cells, level::Level, matrix::Matrix, node::Node},
+    LivePosition, NodeSize, Position,
+};
+
+use super::{assets::GridTextures, camera::GameCamera};
+
+// a tile sprite from the pool, slot is its fixed (row, col) within the pool
+#[derive(Component)]
+pub struct Tile {
+    slot: (usize, usize),
+}
+
+// just enough tiles to cover the window plus a one tile border,
+// cell (row, col) is always drawn by slot (row % rows, col % cols)
+#[derive(Resource, Default)]
+pub struct TilePool {
+    rows: usize,
+    cols: usize,
+    tiles: Vec<Entity>,
+}
+
+impl TilePool {
+    fn tile_at(&self, coordinates: &(usize, usize)) -> Option<Entity> {
+        match self.tiles.is_empty() {
+            true => None,
+            false => Some(
+                self.tiles[(coordinates.0 % self.rows) * self.cols + coordinates.1 % self.cols],
+            ),
+        }
+    }
+}
+
+pub struct GridSpritePlugin;
+
+impl Plugin for GridSpritePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TilePool>()
+            .add_system(resize_pool_system)
+            .add_system(layout_pool_system.after(resize_pool_system))
+            .add_system(retexture_changes_system.after(layout_pool_system))
+            .add_system(render_tile_system.after(retexture_changes_system));
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn resize_pool_system(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    node_size: Res<NodeSize>,
+    camera: Res<GameCamera>,
+    mut pool: ResMut<TilePool>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    // zoom is rounded up to half steps, so that scrolling the wheel doesn't rebuild every frame
+    let zoom = (camera.zoom.max(1.) * 2.).ceil() / 2.;
+    let rows = (window.height() * zoom / node_size.0 .1).ceil() as usize + 2;
+    let cols = (window.width() * zoom / node_size.0 .0).ceil() as usize + 2;
+
+    if pool.rows == rows && pool.cols == cols {
+        return;
+    }
+
+    for entity in pool.tiles.drain(..) {
+        commands.entity(entity).despawn();
+    }
+
+    pool.rows = rows;
+    pool.cols = cols;
+    pool.tiles = (0..rows * cols)
+        .map(|index| {
+            commands
+                .spawn(Tile {
+                    slot: (index / cols, index % cols),
+                })
+                .insert(Node::closed())
+                .insert(Position((0, 0)))
+                .insert(SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::new(node_size.0 .0, node_size.0 .1)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0., 0., 99.),
+                    visibility: Visibility::INVISIBLE,
+                    ..default()
+                })
+                .id()
+        })
+        .collect();
+}
+
+// wraps every slot onto the cell it covers around the camera focus, the work is
+// bound by the pool size and not by the size of the map
+fn layout_pool_system(
+    node_size: Res<NodeSize>,
+    camera: Res<GameCamera>,
+    matrix: Res<Matrix<Node>>,
+    pool: Res<TilePool>,
+    p_query: Query<&LivePosition>,
+    mut query: Query<(
+        &Tile,
+        &mut Position,
+        &mut Node,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+) {
+    if p_query.is_empty() || pool.tiles.is_empty() {
+        return;
+    }
+
+    let live_position = p_query.single();
+    let wrap = |focus: f32, size: usize, slot: usize| {
+        let origin = (focus - 0.5).floor() as i64 - size as i64 / 2;
+
+        origin + (slot as i64 - origin).rem_euclid(size as i64)
+    };
+
+    for (tile, mut position, mut node, mut transform, mut visibility) in &mut query {
+        let row = wrap(camera.focus.0, pool.rows, tile.slot.0);
+        let col = wrap(camera.focus.1, pool.cols, tile.slot.1);
+        let in_bounds = row >= 0 && col >= 0 && matrix.contains(&(row as usize, col as usize));
+
+        if visibility.is_visible != in_bounds {
+            visibility.is_visible = in_bounds;
+        }
+
+        if !in_bounds {
+            continue;
+        }
+
+        let coordinates = (row as usize, col as usize);
+
+        if position.0 != coordinates {
+            position.0 = coordinates;
+        }
+
+        if *node != matrix[coordinates] {
+            *node = matrix[coordinates];
+        }
+
+        let center = node_size.cell_center(live_position, (row as f32, col as f32));
+
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+    }
+}
+
+// an edited cell changes the wall tile of its neighbours, diagonals included
+fn retexture_changes_system(
+    matrix: Res<Matrix<Node>>,
+    pool: Res<TilePool>,
+    mut generation: Local<Option<u64>>,
+    mut query: Query<(&Position, &mut Node), With<Tile>>,
+) {
+    let last_generation = match *generation {
+        Some(value) => value,
+        None => {
+            *generation = Some(matrix.changes.generation());
+
+            return;
+        }
+    };
+
+    if matrix.changes.generation() == last_generation {
+        return;
+    }
+
+    *generation = Some(matrix.changes.generation());
+
+    let cells = match matrix.changes.since(last_generation) {
+        Some(cells) => cells,
+        None => {
+            for (_, mut node) in &mut query {
+                node.set_changed();
+            }
+
+            return;
+        }
+    };
+
+    for coordinates in cells {
+        for neighbour in affected_cells(&coordinates) {
+            if let Some((position, mut node)) = pool
+                .tile_at(&neighbour)
+                .and_then(|entity| query.get_mut(entity).ok())
+            {
+                if position.0 != neighbour || !matrix.contains(&neighbour) {
+                    continue;
+                }
+
+                if *node != matrix[neighbour] {
+                    *node = matrix[neighbour];
+                } else {
+                    node.set_changed();
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn render_tile_system(
+    grid_textures: Res<GridTextures>,
+    matrix: Res<Matrix<Node>>,
+    level: Res<Level>,
+    mut query: Query<
+        (&Position, &mut Handle<Image>, &mut Sprite),
+        (With<Tile>, Or<(Changed<Node>, Changed<Position>)>),
+    >,
+) {
+    for (position, mut handle, mut sprite) in &mut query {
+        (*handle, sprite.rect, sprite.color) =
+            grid_textures.resolve_cell(&matrix, &level, &position.0);
+    }
+}
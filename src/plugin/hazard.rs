@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    game::{
+        chunk::{all_chunks, chunk_of},
+        coordinates::Coordinates,
+        hazard::{Hazard, PIT_DAMAGE, SPIKE_DAMAGE},
+        level::Level,
+    },
+    GameMode, Health, LivePosition, NodeSize, Player, PlayerPosition, Position,
+};
+
+use super::camera::GameCamera;
+
+// pixels per side of the generated hazard images
+const HAZARD_SIZE: usize = 16;
+// teleporters of a pair share the tint, pairs cycle through these
+const TELEPORTER_TINTS: [Color; 4] = [
+    Color::rgb(0.3, 0.9, 1.0),
+    Color::rgb(1.0, 0.4, 0.9),
+    Color::rgb(0.6, 1.0, 0.3),
+    Color::rgb(1.0, 0.7, 0.2),
+];
+
+#[derive(Resource)]
+struct HazardTextures {
+    spikes: Handle<Image>,
+    mud: Handle<Image>,
+    pit: Handle<Image>,
+    teleporter: Handle<Image>,
+}
+
+#[derive(Component)]
+struct HazardTile(Coordinates);
+
+// the sprite of every cell with a hazard and the hazard it shows
+#[derive(Resource, Default)]
+struct HazardTiles(HashMap<Coordinates, (Entity, Hazard)>);
+
+pub struct HazardPlugin;
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HazardTiles>()
+            .add_startup_system(setup_system)
+            .add_system_set(
+                SystemSet::on_update(GameMode::Playing).with_system(player_hazard_system),
+            )
+            .add_system(spawn_tiles_system)
+            .add_system(render_tiles_system.after(spawn_tiles_system));
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn setup_system(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let half = HAZARD_SIZE as f32 / 2.;
+    let distance = move |x: usize, y: usize| {
+        ((x as f32 + 0.5 - half).powi(2) + (y as f32 + 0.5 - half).powi(2)).sqrt()
+    };
+
+    // four rows of four spikes, lit from the left
+    let spikes = generate(&mut images, |x, y| {
+        let (x, y) = (x % 4, y % 4);
+
+        match y.div_ceil(2) >= x.abs_diff(1).max(x.abs_diff(2)) {
+            true if x < 2 => [200, 200, 210, 255],
+            true => [120, 120, 130, 255],
+            false => [0, 0, 0, 0],
+        }
+    });
+    let mud = generate(&mut images, |x, y| match (x * 7 + y * 13) % 11 {
+        0 | 5 => [60, 40, 20, 200],
+        _ => [95, 65, 35, 200],
+    });
+    let pit = generate(&mut images, |x, y| match distance(x, y) {
+        it if it < half - 2. => [5, 5, 8, 255],
+        it if it < half - 0.5 => [40, 35, 30, 255],
+        _ => [0, 0, 0, 0],
+    });
+    // white rings, tinted per pair by the sprite
+    let teleporter = generate(&mut images, |x, y| match distance(x, y) as usize {
+        2 | 5 | 7 => [255, 255, 255, 230],
+        _ => [0, 0, 0, 0],
+    });
+
+    commands.insert_resource(HazardTextures {
+        spikes,
+        mud,
+        pit,
+        teleporter,
+    });
+}
+
+fn generate(images: &mut Assets<Image>, pixel: impl Fn(usize, usize) -> [u8; 4]) -> Handle<Image> {
+    let data = (0..HAZARD_SIZE)
+        .flat_map(|y| (0..HAZARD_SIZE).map(move |x| (x, y)))
+        .flat_map(|(x, y)| pixel(x, y))
+        .collect();
+
+    images.add(Image::new(
+        Extent3d {
+            width: HAZARD_SIZE as u32,
+            height: HAZARD_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    ))
+}
+
+// spikes hurt on every step and keep hurting while standing on them, pits hurt and
+// put the player back where they came from, teleporters move them to the other end
+fn player_hazard_system(
+    time: Res<Time>,
+    level: Res<Level>,
+    mut camera: ResMut<GameCamera>,
+    mut p_query: Query<(&mut PlayerPosition, &mut LivePosition, &mut Health), With<Player>>,
+    mut last_cell: Local<Option<Coordinates>>,
+    mut spike_timer: Local<Timer>,
+) {
+    if p_query.is_empty() {
+        return;
+    }
+
+    let (mut player_position, mut live_position, mut health) = p_query.single_mut();
+    let cell = player_position.current_position.0;
+
+    if !level.hazard.contains(&cell) {
+        return;
+    }
+
+    if *last_cell == Some(cell) {
+        if level.hazard[cell] == Hazard::Spikes && player_position.next_position.is_none() {
+            spike_timer.tick(time.delta());
+
+            if spike_timer.just_finished() {
+                *health = Health(health.0.saturating_sub(SPIKE_DAMAGE));
+            }
+        }
+
+        return;
+    }
+
+    let previous = last_cell.replace(cell);
+    let arrival = match level.hazard[cell] {
+        Hazard::Spikes => {
+            *health = Health(health.0.saturating_sub(SPIKE_DAMAGE));
+            *spike_timer = Timer::from_seconds(1., TimerMode::Repeating);
+
+            None
+        }
+        Hazard::Pit => {
+            *health = Health(health.0.saturating_sub(PIT_DAMAGE));
+
+            previous
+        }
+        Hazard::Teleporter(_) => level.teleporter_exit(cell),
+        Hazard::None | Hazard::Mud => None,
+    };
+
+    if let Some(arrival) = arrival {
+        *player_position = PlayerPosition {
+            current_position: Position(arrival),
+            next_position: None,
+        };
+        *live_position = LivePosition((arrival.0 as f32, arrival.1 as f32));
+        *last_cell = Some(arrival);
+        camera.focus = live_position.0;
+    }
+}
+
+// sprites stay with their cell, only cells whose hazard differs from the sprite are touched
+fn spawn_tiles_system(
+    mut commands: Commands,
+    level: Res<Level>,
+    node_size: Res<NodeSize>,
+    hazard_textures: Res<HazardTextures>,
+    mut tiles: ResMut<HazardTiles>,
+    mut query: Query<(&mut Handle<Image>, &mut Sprite), With<HazardTile>>,
+    mut generation: Local<Option<u64>>,
+) {
+    let last_generation = generation.replace(level.hazard.changes.generation());
+
+    if last_generation == *generation && !level.is_changed() {
+        return;
+    }
+
+    // unloading a chunk isn't logged, its tiles are dropped along with the logged cells
+    let cells: Vec<Coordinates> =
+        match last_generation.and_then(|it| level.hazard.changes.since(it)) {
+            Some(cells) => cells
+                .into_iter()
+                .chain(
+                    tiles
+                        .0
+                        .keys()
+                        .filter(|it| !level.hazard.is_chunk_loaded(&chunk_of(it)))
+                        .copied(),
+                )
+                .collect(),
+            // only loaded chunks can hold hazards, the tiles shown so far are checked to go
+            None => all_chunks(level.hazard.chunk_count())
+                .filter(|it| level.hazard.is_chunk_loaded(it))
+                .flat_map(|it| level.hazard.chunk_cells(&it))
+                .chain(tiles.0.keys().copied())
+                .collect(),
+        };
+
+    for coordinates in cells {
+        let hazard = level.hazard[coordinates];
+        let tile = tiles.0.get(&coordinates).copied();
+
+        if tile.map(|it| it.1) == Some(hazard) {
+            continue;
+        }
+
+        let (texture, color) = match hazard {
+            Hazard::None => {
+                if let Some((entity, _)) = tiles.0.remove(&coordinates) {
+                    commands.entity(entity).despawn();
+                }
+
+                continue;
+            }
+            Hazard::Spikes => (hazard_textures.spikes.clone(), Color::WHITE),
+            Hazard::Mud => (hazard_textures.mud.clone(), Color::WHITE),
+            Hazard::Pit => (hazard_textures.pit.clone(), Color::WHITE),
+            Hazard::Teleporter(pair) => (
+                hazard_textures.teleporter.clone(),
+                TELEPORTER_TINTS[pair as usize % TELEPORTER_TINTS.len()],
+            ),
+        };
+
+        if let Some((entity, _)) = tile {
+            if let Ok((mut handle, mut sprite)) = query.get_mut(entity) {
+                *handle = texture;
+                sprite.color = color;
+            }
+
+            tiles.0.insert(coordinates, (entity, hazard));
+
+            continue;
+        }
+
+        let entity = commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::new(node_size.0 .0, node_size.0 .1)),
+                        ..default()
+                    },
+                    texture,
+                    transform: Transform::from_xyz(0., 0., 99.1),
+                    ..default()
+                },
+                HazardTile(coordinates),
+            ))
+            .id();
+
+        tiles.0.insert(coordinates, (entity, hazard));
+    }
+}
+
+fn render_tiles_system(
+    node_size: Res<NodeSize>,
+    p_query: Query<&LivePosition>,
+    mut query: Query<(&HazardTile, &mut Transform)>,
+) {
+    for live_position in &p_query {
+        for (tile, mut transform) in &mut query {
+            let center = node_size.cell_center(live_position, (tile.0 .0 as f32, tile.0 .1 as f32));
+
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use crate::game::{dungeon::Dungeon, rng::GameRng};
+
+#[derive(Component)]
+struct SeedText;
+
+#[derive(Component)]
+struct FloorText;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_system)
+            .add_system(render_floor_system);
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn setup_system(mut commands: Commands, asset_server: Res<AssetServer>, game_rng: Res<GameRng>) {
+    let font = asset_server.load("DejaVuSansMono.ttf");
+
+    commands.spawn((
+        TextBundle::from_section(
+            format!("seed {}", game_rng.seed),
+            TextStyle {
+                font: font.clone(),
+                font_size: 16.,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(8.),
+                left: Val::Px(8.),
+                ..default()
+            },
+            ..default()
+        }),
+        SeedText,
+    ));
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font,
+                font_size: 16.,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(28.),
+                left: Val::Px(8.),
+                ..default()
+            },
+            ..default()
+        }),
+        FloorText,
+    ));
+}
+
+fn render_floor_system(dungeon: Res<Dungeon>, mut query: Query<&mut Text, With<FloorText>>) {
+    if !dungeon.is_changed() {
+        return;
+    }
+
+    for mut text in &mut query {
+        text.sections[0].value = format!("floor {}", dungeon.floor);
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    game::{
+        chunk::chunk_of, coordinates::Coordinates, dungeon::Dungeon, matrix::Matrix, node::Node,
+    },
+    EnemyType, LivePosition, PlayerPosition, Position,
+};
+
+use super::power_up::PowerUp;
+
+// (width, height) of the minimap frame in pixels
+const MINIMAP_SIZE: f32 = 200.;
+// cells around the player that become explored
+const EXPLORE_RADIUS: usize = 6;
+// enemies further away than this many cells are not shown
+const ENEMY_RADIUS: f32 = 20.;
+// zoom levels relative to the whole map fitting the frame
+const ZOOM_LEVELS: [f32; 4] = [1., 2., 4., 8.];
+
+const UNEXPLORED_COLOR: [u8; 4] = [0, 0, 0, 0];
+const WALL_COLOR: [u8; 4] = [150, 150, 160, 255];
+const FLOOR_COLOR: [u8; 4] = [40, 40, 48, 255];
+
+#[derive(Resource)]
+pub struct Minimap {
+    image: Handle<Image>,
+    zoom: usize,
+    // whether explored cells are walls, as of the last time their chunk was loaded, so that
+    // the minimap keeps showing chunks that are no longer in memory
+    seen: Matrix<Option<bool>>,
+    // seen cells of the floors the player is not on
+    floor: usize,
+    floors: HashMap<usize, Matrix<Option<bool>>>,
+    markers: Vec<Entity>,
+}
+
+impl Minimap {
+    fn draw(&self, image: &mut Image, coordinates: &Coordinates) {
+        let color = match self.seen[*coordinates] {
+            None => UNEXPLORED_COLOR,
+            Some(true) => WALL_COLOR,
+            Some(false) => FLOOR_COLOR,
+        };
+        let offset = (coordinates.0 * self.seen.cols + coordinates.1) * 4;
+
+        image.data[offset..offset + 4].copy_from_slice(&color);
+    }
+}
+
+#[derive(Component)]
+struct MinimapFrame;
+
+#[derive(Component)]
+struct MinimapImage;
+
+#[derive(Component)]
+struct MinimapMarker;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system_to_stage(StartupStage::PostStartup, setup_system)
+            .add_system(input_system)
+            .add_system(change_floor_system)
+            .add_system(explore_system.after(change_floor_system))
+            .add_system(redraw_changes_system)
+            .add_system(layout_system.after(input_system));
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn setup_system(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    matrix: Res<Matrix<Node>>,
+) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: matrix.cols as u32,
+            height: matrix.rows as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &UNEXPLORED_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(8.),
+                        right: Val::Px(8.),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(MINIMAP_SIZE), Val::Px(MINIMAP_SIZE)),
+                    overflow: Overflow::Hidden,
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+            MinimapFrame,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                ImageBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    image: UiImage(image.clone()),
+                    ..default()
+                },
+                MinimapImage,
+            ));
+        });
+
+    commands.insert_resource(Minimap {
+        image,
+        zoom: 0,
+        seen: Matrix::new(matrix.rows, matrix.cols, None),
+        floor: 1,
+        floors: HashMap::new(),
+        markers: Vec::new(),
+    });
+}
+
+fn input_system(
+    keys: Res<Input<KeyCode>>,
+    mut minimap: ResMut<Minimap>,
+    mut query: Query<&mut Visibility, With<MinimapFrame>>,
+) {
+    if keys.just_pressed(KeyCode::M) {
+        for mut visibility in &mut query {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+
+    if keys.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd]) {
+        minimap.zoom = (minimap.zoom + 1).min(ZOOM_LEVELS.len() - 1);
+    } else if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        minimap.zoom = minimap.zoom.saturating_sub(1);
+    }
+}
+
+// the floor is compared instead of waiting for the event, so that cells explored on arrival
+// are never stored with the floor that was left
+fn change_floor_system(
+    dungeon: Res<Dungeon>,
+    matrix: Res<Matrix<Node>>,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if minimap.floor == dungeon.floor {
+        return;
+    }
+
+    let seen = minimap
+        .floors
+        .remove(&dungeon.floor)
+        .unwrap_or_else(|| Matrix::new(matrix.rows, matrix.cols, None));
+    let previous = std::mem::replace(&mut minimap.seen, seen);
+    let previous_floor = minimap.floor;
+
+    minimap.floors.insert(previous_floor, previous);
+    minimap.floor = dungeon.floor;
+
+    if let Some(image) = images.get_mut(&minimap.image) {
+        for row in 0..matrix.rows {
+            for col in 0..matrix.cols {
+                minimap.draw(image, &(row, col));
+            }
+        }
+    }
+}
+
+fn explore_system(
+    matrix: Res<Matrix<Node>>,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    query: Query<&PlayerPosition, Changed<PlayerPosition>>,
+) {
+    for player_position in &query {
+        let center = player_position.current_position.0;
+        let rows = center.0.saturating_sub(EXPLORE_RADIUS)..(center.0 + EXPLORE_RADIUS + 1);
+        let cols = center.1.saturating_sub(EXPLORE_RADIUS)..(center.1 + EXPLORE_RADIUS + 1);
+        let unexplored: Vec<Coordinates> = rows
+            .flat_map(|row| cols.clone().map(move |col| (row, col)))
+            .filter(|it| {
+                matrix.contains(it)
+                    && matrix.is_chunk_loaded(&chunk_of(it))
+                    && minimap.seen[*it].is_none()
+            })
+            .collect();
+
+        if unexplored.is_empty() {
+            continue;
+        }
+
+        if let Some(image) = images.get_mut(&minimap.image) {
+            for coordinates in unexplored {
+                minimap.seen[coordinates] = Some(matrix[coordinates].is_wall());
+                minimap.draw(image, &coordinates);
+            }
+        }
+    }
+}
+
+fn redraw_changes_system(
+    matrix: Res<Matrix<Node>>,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    mut generation: Local<Option<u64>>,
+) {
+    let last_generation = match *generation {
+        Some(value) => value,
+        None => {
+            *generation = Some(matrix.changes.generation());
+
+            return;
+        }
+    };
+
+    if matrix.changes.generation() == last_generation {
+        return;
+    }
+
+    *generation = Some(matrix.changes.generation());
+
+    let cells = match matrix.changes.since(last_generation) {
+        Some(cells) => cells,
+        None => (0..matrix.rows)
+            .flat_map(|row| (0..matrix.cols).map(move |col| (row, col)))
+            .collect(),
+    };
+
+    // cells of unloaded chunks keep what was seen of them
+    let cells: Vec<Coordinates> = cells
+        .into_iter()
+        .filter(|it| minimap.seen[*it].is_some() && matrix.is_chunk_loaded(&chunk_of(it)))
+        .collect();
+
+    if let Some(image) = images.get_mut(&minimap.image) {
+        for coordinates in cells {
+            minimap.seen[coordinates] = Some(matrix[coordinates].is_wall());
+            minimap.draw(image, &coordinates);
+        }
+    }
+}
+
+// the whole map fits the frame at the first zoom level, the other levels follow the player
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn layout_system(
+    mut commands: Commands,
+    matrix: Res<Matrix<Node>>,
+    mut minimap: ResMut<Minimap>,
+    p_query: Query<&LivePosition>,
+    e_query: Query<&Position, With<EnemyType>>,
+    u_query: Query<&Position, With<PowerUp>>,
+    mut i_query: Query<(Entity, &mut Style), (With<MinimapImage>, Without<MinimapMarker>)>,
+    mut m_query: Query<
+        (&mut Style, &mut BackgroundColor, &mut Visibility),
+        (With<MinimapMarker>, Without<MinimapImage>),
+    >,
+) {
+    if p_query.is_empty() || i_query.is_empty() {
+        return;
+    }
+
+    let live_position = p_query.single();
+    let (image_entity, mut image_style) = i_query.single_mut();
+    let scale = MINIMAP_SIZE / matrix.rows.max(matrix.cols) as f32 * ZOOM_LEVELS[minimap.zoom];
+    let (top, left) = match minimap.zoom {
+        0 => (
+            (MINIMAP_SIZE - matrix.rows as f32 * scale) / 2.,
+            (MINIMAP_SIZE - matrix.cols as f32 * scale) / 2.,
+        ),
+        _ => (
+            MINIMAP_SIZE / 2. - live_position.0 .0 * scale,
+            MINIMAP_SIZE / 2. - live_position.0 .1 * scale,
+        ),
+    };
+
+    image_style.size = Size::new(
+        Val::Px(matrix.cols as f32 * scale),
+        Val::Px(matrix.rows as f32 * scale),
+    );
+    image_style.position = UiRect {
+        top: Val::Px(top),
+        left: Val::Px(left),
+        ..default()
+    };
+
+    let enemies = e_query.iter().map(|it| it.0).filter(|it| {
+        (it.0 as f32 - live_position.0 .0).abs() <= ENEMY_RADIUS
+            && (it.1 as f32 - live_position.0 .1).abs() <= ENEMY_RADIUS
+    });
+    let power_ups = u_query
+        .iter()
+        .map(|it| it.0)
+        .filter(|it| minimap.seen[*it].is_some());
+    let dots: Vec<((f32, f32), Color)> = std::iter::once((live_position.0, Color::GREEN))
+        .chain(enemies.map(|it| ((it.0 as f32, it.1 as f32), Color::RED)))
+        .chain(power_ups.map(|it| ((it.0 as f32, it.1 as f32), Color::YELLOW)))
+        .collect();
+
+    // markers are reused between frames, the pool only ever grows
+    while minimap.markers.len() < dots.len() {
+        let marker = commands.spawn((NodeBundle::default(), MinimapMarker)).id();
+
+        commands.entity(image_entity).add_child(marker);
+        minimap.markers.push(marker);
+    }
+
+    let size = scale.max(3.);
+
+    for (index, entity) in minimap.markers.iter().enumerate() {
+        if let Ok((mut style, mut background_color, mut visibility)) = m_query.get_mut(*entity) {
+            match dots.get(index) {
+                Some(((row, col), color)) => {
+                    *style = Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            top: Val::Px((row + 0.5) * scale - size / 2.),
+                            left: Val::Px((col + 0.5) * scale - size / 2.),
+                            ..default()
+                        },
+                        size: Size::new(Val::Px(size), Val::Px(size)),
+                        ..default()
+                    };
+                    *background_color = (*color).into();
+                    visibility.is_visible = true;
+                }
+                None => visibility.is_visible = false,
+            }
+        }
+    }
+}
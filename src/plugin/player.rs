@@ -1,13 +1,32 @@
+use std::cmp::Ordering;
+
 use bevy::prelude::*;
-use rand::prelude::*;
 
 use crate::{
-    game::matrix::Matrix, game::movement::Movement, game::node::Node, Health, LivePosition,
-    NodeSize, Player, PlayerPosition, PlayerSprites, Position, WalkAnimationTimer,
+    game::coordinates::Coordinates,
+    game::door::{AgentGrid, AgentRules, Doors},
+    game::level::{Level, PlacementKind},
+    game::matrix::Matrix,
+    game::movement::Movement,
+    game::node::Node,
+    Faction, GameMode, Health, LivePosition, NodeSize, Player, PlayerPosition, PlayerSprites,
+    Position, WalkAnimationTimer,
 };
 
 use super::{grid::OpenNodes, projectile::ProjectilePlugin};
 
+// seconds after a hit during which further hits are ignored
+const INVULNERABLE_TIME: f32 = 1.;
+// seconds per color while flashing
+const FLASH_TIME: f32 = 0.1;
+const HURT_COLOR: Color = Color::rgb(1., 0.3, 0.3);
+
+// sent by anything that hurts the player on contact, from is the cell the hit came from
+pub struct PlayerHit {
+    pub damage: u16,
+    pub from: Coordinates,
+}
+
 pub struct PlayerPlugin;
 
 #[derive(Component)]
@@ -18,9 +37,18 @@ struct KeyState {
 #[derive(Component, Deref, DerefMut)]
 struct AnimationTimer(Timer);
 
+#[derive(Component, Deref, DerefMut)]
+struct Invulnerable(Timer);
+
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system_to_stage(StartupStage::PostStartup, setup_system)
+        app.add_event::<PlayerHit>()
+            .add_startup_system_to_stage(StartupStage::PostStartup, setup_system)
+            .add_system_set(
+                SystemSet::on_update(GameMode::Playing)
+                    .with_system(hurt_system)
+                    .with_system(flash_system),
+            )
             .add_system(update_player_position_system)
             .add_system(traverse_path)
             .add_system(update_sprite)
@@ -37,10 +65,14 @@ fn setup_system(
     mut commands: Commands,
     node_size: Res<NodeSize>,
     open_nodes: Res<OpenNodes>,
+    level: Res<Level>,
     player_sprites: Res<PlayerSprites>,
 ) {
-    let mut rng = rand::thread_rng();
-    let start_position = open_nodes.0[(rng.gen::<f32>() * open_nodes.0.len() as f32) as usize];
+    // a map without a single open cell still needs the player somewhere
+    let start_position = level
+        .first_of(PlacementKind::PlayerSpawn)
+        .or_else(|| open_nodes.0.first().copied())
+        .unwrap_or_default();
 
     commands
         .spawn_empty()
@@ -50,6 +82,8 @@ fn setup_system(
             next_position: None,
         })
         .insert(Health(100))
+        .insert(Faction::Player)
+        .insert(AgentRules::player())
         .insert(LivePosition((0., 0.)))
         .insert((
             SpriteSheetBundle {
@@ -77,10 +111,13 @@ fn setup_system(
 
 fn update_player_position_system(
     key_code: Res<Input<KeyCode>>,
-    mut query: Query<(&mut PlayerPosition, &mut KeyState), With<Player>>,
+    mut query: Query<(&mut PlayerPosition, &mut KeyState, &AgentRules), With<Player>>,
     matrix: Res<Matrix<Node>>,
+    doors: Res<Doors>,
 ) {
-    for (mut position, mut key_state) in &mut query {
+    for (mut position, mut key_state, rules) in &mut query {
+        let grid = AgentGrid::new(&matrix, &doors, rules);
+
         if key_code.just_released(KeyCode::Left) && key_state.down_key == Some(KeyCode::Left) {
             *key_state = KeyState { down_key: None };
         } else if key_code.just_released(KeyCode::Right)
@@ -114,28 +151,28 @@ fn update_player_position_system(
 
         if position.next_position.is_none() {
             if key_code.just_pressed(KeyCode::Left) {
-                if let Some(next_node) = matrix.left(&position.current_position.0) {
+                if let Some(next_node) = grid.left(&position.current_position.0) {
                     *position = PlayerPosition {
                         current_position: position.current_position,
                         next_position: Some(Position(next_node)),
                     };
                 }
             } else if key_code.just_pressed(KeyCode::Right) {
-                if let Some(next_node) = matrix.right(&position.current_position.0) {
+                if let Some(next_node) = grid.right(&position.current_position.0) {
                     *position = PlayerPosition {
                         current_position: position.current_position,
                         next_position: Some(Position(next_node)),
                     };
                 }
             } else if key_code.just_pressed(KeyCode::Up) {
-                if let Some(next_node) = matrix.up(&position.current_position.0) {
+                if let Some(next_node) = grid.up(&position.current_position.0) {
                     *position = PlayerPosition {
                         current_position: position.current_position,
                         next_position: Some(Position(next_node)),
                     };
                 }
             } else if key_code.just_pressed(KeyCode::Down) {
-                if let Some(next_node) = matrix.down(&position.current_position.0) {
+                if let Some(next_node) = grid.down(&position.current_position.0) {
                     *position = PlayerPosition {
                         current_position: position.current_position,
                         next_position: Some(Position(next_node)),
@@ -146,6 +183,7 @@ fn update_player_position_system(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn traverse_path(
     time: Res<Time>,
     mut query: Query<
@@ -155,10 +193,13 @@ fn traverse_path(
             &mut Visibility,
             &mut LivePosition,
             &KeyState,
+            &AgentRules,
         ),
         With<Player>,
     >,
     matrix: Res<Matrix<Node>>,
+    level: Res<Level>,
+    doors: Res<Doors>,
 ) {
     for (
         mut walk_animation_timer,
@@ -166,10 +207,14 @@ fn traverse_path(
         mut visibility,
         mut live_position,
         key_state,
+        rules,
     ) in &mut query
     {
+        let grid = AgentGrid::new(&matrix, &doors, rules);
         let l_p = if let Some(next_position) = player_position.next_position {
-            walk_animation_timer.tick(time.delta());
+            let pace = level.hazard[player_position.current_position.0].pace();
+
+            walk_animation_timer.tick(time.delta().mul_f32(pace));
 
             let mut delta_factor = walk_animation_timer.elapsed().as_millis() as f32
                 / walk_animation_timer.duration().as_millis() as f32;
@@ -182,7 +227,7 @@ fn traverse_path(
                 if let Some(down_key) = key_state.down_key {
                     match down_key {
                         KeyCode::Left => {
-                            if let Some(next_node) = matrix.left(&next_position.0) {
+                            if let Some(next_node) = grid.left(&next_position.0) {
                                 *player_position = PlayerPosition {
                                     current_position: next_position,
                                     next_position: Some(Position(next_node)),
@@ -195,7 +240,7 @@ fn traverse_path(
                             }
                         }
                         KeyCode::Right => {
-                            if let Some(next_node) = matrix.right(&next_position.0) {
+                            if let Some(next_node) = grid.right(&next_position.0) {
                                 *player_position = PlayerPosition {
                                     current_position: next_position,
                                     next_position: Some(Position(next_node)),
@@ -208,7 +253,7 @@ fn traverse_path(
                             }
                         }
                         KeyCode::Up => {
-                            if let Some(next_node) = matrix.up(&next_position.0) {
+                            if let Some(next_node) = grid.up(&next_position.0) {
                                 *player_position = PlayerPosition {
                                     current_position: next_position,
                                     next_position: Some(Position(next_node)),
@@ -221,7 +266,7 @@ fn traverse_path(
                             }
                         }
                         KeyCode::Down => {
-                            if let Some(next_node) = matrix.down(&next_position.0) {
+                            if let Some(next_node) = grid.down(&next_position.0) {
                                 *player_position = PlayerPosition {
                                     current_position: next_position,
                                     next_position: Some(Position(next_node)),
@@ -301,3 +346,81 @@ fn animate_sprite(
         }
     }
 }
+
+#[allow(clippy::type_complexity)]
+// the strongest hit of the frame counts, it knocks the player one cell away from where it came
+// from unless a wall or a door is in the way
+fn hurt_system(
+    mut commands: Commands,
+    matrix: Res<Matrix<Node>>,
+    doors: Res<Doors>,
+    mut hit_events: EventReader<PlayerHit>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Health,
+            &mut PlayerPosition,
+            &mut LivePosition,
+            &AgentRules,
+        ),
+        (With<Player>, Without<Invulnerable>),
+    >,
+) {
+    let hit = match hit_events.iter().max_by_key(|it| it.damage) {
+        Some(hit) => hit,
+        None => return,
+    };
+    let (entity, mut health, mut player_position, mut live_position, rules) =
+        match query.get_single_mut() {
+            Ok(player) => player,
+            Err(_) => return,
+        };
+
+    *health = Health(health.0.saturating_sub(hit.damage));
+    commands
+        .entity(entity)
+        .insert(Invulnerable(Timer::from_seconds(
+            INVULNERABLE_TIME,
+            TimerMode::Once,
+        )));
+
+    let cell = player_position.current_position.0;
+    let grid = AgentGrid::new(&matrix, &doors, rules);
+    let away = match (cell.0.cmp(&hit.from.0), cell.1.cmp(&hit.from.1)) {
+        (Ordering::Less, _) => grid.up(&cell),
+        (Ordering::Greater, _) => grid.down(&cell),
+        (_, Ordering::Less) => grid.left(&cell),
+        (_, Ordering::Greater) => grid.right(&cell),
+        _ => None,
+    };
+
+    if let Some(away) = away.filter(|it| doors.crossed(&cell, it).is_none()) {
+        *player_position = PlayerPosition {
+            current_position: Position(away),
+            next_position: None,
+        };
+        *live_position = LivePosition((away.0 as f32, away.1 as f32));
+    }
+}
+
+fn flash_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Invulnerable, &mut TextureAtlasSprite), With<Player>>,
+) {
+    for (entity, mut invulnerable, mut sprite) in &mut query {
+        invulnerable.tick(time.delta());
+
+        if invulnerable.finished() {
+            sprite.color = Color::WHITE;
+            commands.entity(entity).remove::<Invulnerable>();
+        } else {
+            let flashes = (invulnerable.elapsed_secs() / FLASH_TIME) as usize;
+
+            sprite.color = match flashes % 2 {
+                0 => HURT_COLOR,
+                _ => Color::WHITE,
+            };
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use bevy::prelude::*;
-use rand::prelude::*;
 
-use crate::{LivePosition, NodeSize, PlayerPosition, Position, PowerUpSprites, WalkAnimationTimer};
+use crate::{
+    game::level::{Level, PlacementKind},
+    GameMode, LivePosition, NodeSize, PlayerPosition, Position, PowerUpSprites, WalkAnimationTimer,
+};
 
-use super::{grid::OpenNodes, projectile::ProjectileCount};
+use super::{floor::FloorChanged, projectile::ProjectileCount};
 
 enum PowerUpType {
     Speed,
@@ -11,7 +13,7 @@ enum PowerUpType {
 }
 
 #[derive(Component)]
-struct PowerUp {
+pub(crate) struct PowerUp {
     power_up_type: PowerUpType,
 }
 
@@ -20,32 +22,69 @@ pub struct PowerUpPlugin;
 impl Plugin for PowerUpPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system_to_stage(StartupStage::PostStartup, setup_system)
+            .add_system(change_floor_system)
+            .add_system_set(SystemSet::on_exit(GameMode::Editing).with_system(leave_editor_system))
             .add_system(render_system)
             .add_system(player_obtains_system);
     }
 }
 
-fn setup_system(
-    open_nodes: Res<OpenNodes>,
+fn setup_system(level: Res<Level>, power_up_sprites: Res<PowerUpSprites>, mut commands: Commands) {
+    spawn_power_ups(&level, &power_up_sprites, &mut commands);
+}
+
+// power-ups that were picked up are gone from the level, so a restored floor only respawns the rest
+fn change_floor_system(
+    mut commands: Commands,
+    mut floor_changed_events: EventReader<FloorChanged>,
+    level: Res<Level>,
     power_up_sprites: Res<PowerUpSprites>,
+    query: Query<Entity, With<PowerUp>>,
+) {
+    if floor_changed_events.iter().last().is_none() {
+        return;
+    }
+
+    respawn_power_ups(&level, &power_up_sprites, &mut commands, &query);
+}
+
+// power-ups placed or erased in the editor are picked up once playing again
+fn leave_editor_system(
     mut commands: Commands,
+    level: Res<Level>,
+    power_up_sprites: Res<PowerUpSprites>,
+    query: Query<Entity, With<PowerUp>>,
+) {
+    respawn_power_ups(&level, &power_up_sprites, &mut commands, &query);
+}
+
+fn respawn_power_ups(
+    level: &Level,
+    power_up_sprites: &PowerUpSprites,
+    commands: &mut Commands,
+    query: &Query<Entity, With<PowerUp>>,
 ) {
-    let mut rng = rand::thread_rng();
-    let len = open_nodes.0.len();
+    for entity in query {
+        commands.entity(entity).despawn();
+    }
+
+    spawn_power_ups(level, power_up_sprites, commands);
+}
 
-    (0..100).for_each(|_| {
-        let index = rng.gen_range(0..len);
-        let pu_type = rng.gen_range(0..2);
-        let position = open_nodes.0[index];
+fn spawn_power_ups(level: &Level, power_up_sprites: &PowerUpSprites, commands: &mut Commands) {
+    level.placements.iter().for_each(|placement| {
+        let (power_up_type, texture_atlas) = match placement.kind {
+            PlacementKind::ProjectilePowerUp => (
+                PowerUpType::ProjectileCount,
+                power_up_sprites.projectile_count.clone(),
+            ),
+            PlacementKind::SpeedPowerUp => (PowerUpType::Speed, power_up_sprites.speed.clone()),
+            _ => return,
+        };
 
         commands
-            .spawn(Position(position))
-            .insert(PowerUp {
-                power_up_type: match pu_type {
-                    0 => PowerUpType::ProjectileCount,
-                    _ => PowerUpType::Speed,
-                },
-            })
+            .spawn(Position(placement.coordinates))
+            .insert(PowerUp { power_up_type })
             .insert(SpriteSheetBundle {
                 transform: Transform {
                     translation: Vec3 {
@@ -55,10 +94,7 @@ fn setup_system(
                     },
                     ..default()
                 },
-                texture_atlas: match pu_type {
-                    0 => power_up_sprites.projectile_count.clone(),
-                    _ => power_up_sprites.speed.clone(),
-                },
+                texture_atlas,
                 visibility: Visibility::INVISIBLE,
                 ..default()
             });
@@ -72,10 +108,11 @@ fn render_system(
 ) {
     for (position, mut transform, mut visibility) in &mut query {
         for live_position in &p_query {
-            transform.translation.x =
-                (position.0 .1 as f32 - live_position.0 .1) * node_size.0 .0 + node_size.0 .0 / 2.;
-            transform.translation.y =
-                (live_position.0 .0 - position.0 .0 as f32) * node_size.0 .1 - node_size.0 .1 / 2.;
+            let center =
+                node_size.cell_center(live_position, (position.0 .0 as f32, position.0 .1 as f32));
+
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
 
             *visibility = Visibility::VISIBLE;
         }
@@ -84,6 +121,7 @@ fn render_system(
 
 fn player_obtains_system(
     mut commands: Commands,
+    mut level: ResMut<Level>,
     query: Query<(Entity, &Position, &PowerUp)>,
     mut p_query: Query<(&PlayerPosition, &mut WalkAnimationTimer)>,
     mut projectile_query: Query<&mut ProjectileCount>,
@@ -96,12 +134,16 @@ fn player_obtains_system(
 
                     match power_up.power_up_type {
                         PowerUpType::Speed => {
+                            level.remove(PlacementKind::SpeedPowerUp, position.0);
+
                             *walk_animation_timer = WalkAnimationTimer(Timer::from_seconds(
                                 walk_animation_timer.duration().as_secs_f32() - 0.02,
                                 TimerMode::Repeating,
                             ))
                         }
                         PowerUpType::ProjectileCount => {
+                            level.remove(PlacementKind::ProjectilePowerUp, position.0);
+
                             for mut projectile_count in &mut projectile_query {
                                 *projectile_count = ProjectileCount(projectile_count.0 + 1);
                             }
@@ -4,10 +4,19 @@ use bevy::prelude::*;
 
 use crate::{
     game::{astar::manhattan_heuristic, coordinates::Coordinates, matrix::Matrix, node::Node},
-    Durability, LivePosition, NodeSize, Path, Player, PlayerPosition, ProjectilePosition,
-    ProjectileReach, ProjectileSprites, TraversalIndex,
+    Durability, Faction, GameMode, LivePosition, NodeSize, Path, Player, PlayerPosition,
+    ProjectilePosition, ProjectileReach, ProjectileSprites, TraversalIndex,
 };
 
+use super::{
+    floor::FloorChanged,
+    player::PlayerHit,
+    wall::{Explosion, WallHit},
+};
+
+// enemy shots use the knife tinted so that they stand out from the player's
+const ENEMY_TINT: Color = Color::rgb(1., 0.45, 0.3);
+
 #[derive(Component, Deref, DerefMut)]
 struct AnimationTimer(Timer);
 
@@ -26,6 +35,10 @@ pub(crate) struct Projectile {
 #[derive(Component)]
 struct Angle(f32);
 
+// blast radius in cells of projectiles that blow up when they hit a wall
+#[derive(Component)]
+pub(crate) struct Explosive(pub(crate) usize);
+
 pub struct ProjectilePlugin;
 
 impl Plugin for ProjectilePlugin {
@@ -33,7 +46,11 @@ impl Plugin for ProjectilePlugin {
         app.add_startup_system_to_stage(StartupStage::PostStartup, setup_system)
             .add_system(launch_projectiles)
             .add_system(animate_projectiles)
-            .add_system(hit_test_projectiles);
+            .add_system(hit_test_projectiles)
+            .add_system_set(
+                SystemSet::on_update(GameMode::Playing).with_system(hit_test_player_system),
+            )
+            .add_system(change_floor_system);
     }
 
     fn name(&self) -> &str {
@@ -124,52 +141,158 @@ fn launch_projectiles(
                         - angle_delta
                         + i as f32 * degree_delta;
 
-                    commands.spawn((
-                        SpriteSheetBundle {
-                            texture_atlas: projectile_sprites.knife.clone(),
-                            transform: Transform {
-                                scale: Vec3::splat(node_size.0 .0 as f32 / projectile_sprites.size),
-                                rotation: Quat::from_rotation_z(angle),
-                                translation: Vec3 {
-                                    x: node_size.0 .0 / 2.,
-                                    y: -node_size.0 .1 / 2.,
-                                    z: 100.,
-                                },
-                                ..default()
-                            },
-                            visibility: Visibility::INVISIBLE,
-                            ..default()
-                        },
-                        Angle(angle),
-                        AnimationTimer(Timer::from_seconds(1. / 60., TimerMode::Repeating)),
-                        Durability(30),
-                        ProjectilePosition(l.0),
-                    ));
+                    spawn_projectile(
+                        &mut commands,
+                        &node_size,
+                        &projectile_sprites,
+                        l.0,
+                        angle,
+                        30,
+                        Faction::Player,
+                    );
                 }
             }
         }
     }
 }
 
+// the angle points back at where the projectile comes from, it flies a sixth of a cell per tick
+pub(crate) fn spawn_projectile(
+    commands: &mut Commands,
+    node_size: &NodeSize,
+    projectile_sprites: &ProjectileSprites,
+    from: (f32, f32),
+    angle: f32,
+    durability: u16,
+    faction: Faction,
+) -> Entity {
+    commands
+        .spawn((
+            SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    color: match faction {
+                        Faction::Player => Color::WHITE,
+                        Faction::Enemy => ENEMY_TINT,
+                    },
+                    ..default()
+                },
+                texture_atlas: projectile_sprites.knife.clone(),
+                transform: Transform {
+                    scale: Vec3::splat(node_size.0 .0 / projectile_sprites.size),
+                    rotation: Quat::from_rotation_z(angle),
+                    translation: Vec3 {
+                        x: node_size.0 .0 / 2.,
+                        y: -node_size.0 .1 / 2.,
+                        z: 100.,
+                    },
+                    ..default()
+                },
+                visibility: Visibility::INVISIBLE,
+                ..default()
+            },
+            Angle(angle),
+            AnimationTimer(Timer::from_seconds(1. / 60., TimerMode::Repeating)),
+            Durability(durability),
+            ProjectilePosition(from),
+            faction,
+        ))
+        .id()
+}
+
+// projectiles break on walls, dealing whatever durability they have left, explosive ones to
+// every wall around
 fn hit_test_projectiles(
     mut commands: Commands,
     matrix: Res<Matrix<Node>>,
-    query: Query<(Entity, &ProjectilePosition), Changed<ProjectilePosition>>,
+    mut wall_hit_events: EventWriter<WallHit>,
+    mut explosion_events: EventWriter<Explosion>,
+    query: Query<
+        (Entity, &ProjectilePosition, &Durability, Option<&Explosive>),
+        Changed<ProjectilePosition>,
+    >,
 ) {
-    for (entity, position) in &query {
+    for (entity, position, durability, explosive) in &query {
         let (row, col) = (position.0 .0, position.0 .1);
 
         if row < 0.
             || row.round() as usize >= matrix.rows
             || col < 0.
             || col.round() as usize >= matrix.cols
-            || matrix[(row.round() as usize, col.round() as usize)] == Node::closed()
         {
-            return commands.entity(entity).despawn();
+            commands.entity(entity).despawn();
+
+            continue;
+        }
+
+        let coordinates = (row.round() as usize, col.round() as usize);
+
+        if matrix[coordinates] == Node::closed() {
+            match explosive {
+                Some(explosive) => explosion_events.send(Explosion {
+                    center: coordinates,
+                    radius: explosive.0,
+                    damage: durability.0,
+                }),
+                None => wall_hit_events.send(WallHit {
+                    coordinates,
+                    damage: durability.0,
+                }),
+            }
+
+            commands.entity(entity).despawn();
         }
     }
 }
 
+// shots of the other side hurt the player with their durability, knocking them away from
+// the cell the shot came through
+fn hit_test_player_system(
+    mut commands: Commands,
+    mut hit_events: EventWriter<PlayerHit>,
+    query: Query<(Entity, &ProjectilePosition, &Angle, &Durability, &Faction)>,
+    p_query: Query<(&LivePosition, &Faction), With<Player>>,
+) {
+    let (live_position, player_faction) = match p_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    for (entity, position, angle, durability, faction) in &query {
+        let (row, col) = position.0;
+
+        if faction == player_faction
+            || (row - live_position.0 .0).abs() >= 0.5
+            || (col - live_position.0 .1).abs() >= 0.5
+        {
+            continue;
+        }
+
+        hit_events.send(PlayerHit {
+            damage: durability.0,
+            from: (
+                (row + angle.0.cos()).round().max(0.) as usize,
+                (col + angle.0.sin()).round().max(0.) as usize,
+            ),
+        });
+        commands.entity(entity).despawn();
+    }
+}
+
+// projectiles in flight belong to the floor that was left
+fn change_floor_system(
+    mut commands: Commands,
+    mut floor_changed_events: EventReader<FloorChanged>,
+    query: Query<Entity, With<ProjectilePosition>>,
+) {
+    if floor_changed_events.iter().last().is_none() {
+        return;
+    }
+
+    for entity in &query {
+        commands.entity(entity).despawn();
+    }
+}
+
 fn animate_projectiles(
     time: Res<Time>,
     node_size: Res<NodeSize>,
@@ -197,10 +320,10 @@ fn animate_projectiles(
                 initial_position.0 .1 - angle.0.sin() / 6.,
             ));
 
-            transform.translation.x =
-                (initial_position.0 .1 - live_position.0 .1) * node_size.0 .0 + node_size.0 .0 / 2.;
-            transform.translation.y =
-                (live_position.0 .0 - initial_position.0 .0) * node_size.0 .1 - node_size.0 .1 / 2.;
+            let center = node_size.cell_center(live_position, initial_position.0);
+
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
 
             if !visibility.is_visible {
                 *visibility = Visibility::VISIBLE;
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    game::{
+        coordinates::Coordinates,
+        door::{AgentGrid, AgentRules, Doors},
+        level::Level,
+        matrix::Matrix,
+        node::Node,
+        spawn::{can_spawn_at, in_spawn_zone, nearby_spawn_zones, path_distances_within},
+        wave::WaveTable,
+    },
+    NodeSize, Player, PlayerPosition,
+};
+
+use super::{boss::SPAWN_DISTANCE, camera::GameCamera};
+
+// how many cells past the edge of the screen still count as just outside of it
+const OFFSCREEN_MARGIN: usize = 6;
+// cells tried when nothing just outside of the screen will do
+const SPAWN_TRIES: usize = 100;
+
+// path distances from the player out to the widest ring to every cell an enemy can appear
+// on, redone once the player steps into another cell or the walls, hazards or doors change,
+// the spawn zones near the player and the corners of the screen as of the start of the frame
+#[derive(Resource, Default)]
+pub(crate) struct SpawnPlacer {
+    from: Option<Coordinates>,
+    generations: (u64, u64),
+    distances: HashMap<Coordinates, u32>,
+    zones: Vec<Coordinates>,
+    reachable: Vec<Coordinates>,
+    view: ((f32, f32), (f32, f32)),
+}
+
+impl SpawnPlacer {
+    fn on_screen(&self, coordinates: &Coordinates) -> bool {
+        let ((top, left), (bottom, right)) = self.view;
+        let (row, col) = (coordinates.0 as f32, coordinates.1 as f32);
+
+        row + 1. > top && row < bottom && col + 1. > left && col < right
+    }
+
+    fn distance(&self, coordinates: &Coordinates) -> Option<u32> {
+        self.distances.get(coordinates).copied()
+    }
+
+    // a cell the player can walk to that is off screen and inside the spawn zones, picked from
+    // the band just outside of the screen within the ring of path distances when there is one,
+    // then from anywhere in the band as far as the distances go, then from anywhere off screen
+    // within the ring
+    pub(crate) fn pick(
+        &self,
+        rng: &mut StdRng,
+        ring: (usize, usize),
+        fits: impl Fn(&Coordinates) -> bool,
+    ) -> Option<Coordinates> {
+        self.from?;
+
+        let (min, max) = (ring.0 as u32, ring.1 as u32);
+        let valid = |it: &Coordinates| {
+            !self.on_screen(it)
+                && self.distance(it).is_some_and(|it| it >= min)
+                && in_spawn_zone(&self.zones, it)
+                && fits(it)
+        };
+        let ((top, left), (bottom, right)) = self.view;
+        // cells off the floor have no distance, so the band needs no clamping
+        let rows = (top.floor().max(0.) as usize).saturating_sub(OFFSCREEN_MARGIN)
+            ..bottom.ceil().max(0.) as usize + OFFSCREEN_MARGIN;
+        let cols = (left.floor().max(0.) as usize).saturating_sub(OFFSCREEN_MARGIN)
+            ..right.ceil().max(0.) as usize + OFFSCREEN_MARGIN;
+        let band: Vec<Coordinates> = rows
+            .flat_map(|row| cols.clone().map(move |col| (row, col)))
+            .filter(valid)
+            .collect();
+        let in_ring: Vec<Coordinates> = band
+            .iter()
+            .filter(|it| self.distance(it).is_some_and(|it| it <= max))
+            .copied()
+            .collect();
+
+        in_ring
+            .choose(rng)
+            .or_else(|| band.choose(rng))
+            .copied()
+            .or_else(|| {
+                (0..SPAWN_TRIES)
+                    .filter_map(|_| self.reachable.choose(rng).copied())
+                    .find(|it| valid(it) && self.distance(it).is_some_and(|it| it <= max))
+            })
+    }
+}
+
+pub struct SpawnPlugin;
+
+impl Plugin for SpawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnPlacer>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_system);
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+// enemies walk to the player by the same rules, so the distances are theirs
+fn update_system(
+    windows: Res<Windows>,
+    camera: Res<GameCamera>,
+    node_size: Res<NodeSize>,
+    matrix: Res<Matrix<Node>>,
+    level: Res<Level>,
+    doors: Res<Doors>,
+    wave_table: Res<WaveTable>,
+    mut placer: ResMut<SpawnPlacer>,
+    query: Query<&PlayerPosition, With<Player>>,
+) {
+    if let Some(window) = windows.get_primary() {
+        placer.view = camera.visible_cells(window, &node_size);
+    }
+
+    let player = match query.get_single() {
+        Ok(player_position) => player_position.current_position.0,
+        Err(_) => return,
+    };
+
+    // nothing is placed further away than the widest ring
+    let max_distance = wave_table
+        .waves
+        .iter()
+        .map(|it| it.ring.1)
+        .chain([SPAWN_DISTANCE.1])
+        .max()
+        .unwrap_or_default();
+
+    if level.is_changed() || placer.from != Some(player) {
+        placer.zones = nearby_spawn_zones(&level, &player, max_distance);
+    }
+
+    let generations = (
+        matrix.changes.generation(),
+        level.hazard.changes.generation(),
+    );
+
+    if placer.from == Some(player) && placer.generations == generations && !doors.is_changed() {
+        return;
+    }
+
+    let rules = AgentRules::enemy();
+    let distances: Vec<(Coordinates, u32)> = path_distances_within(
+        &AgentGrid::new(&matrix, &doors, &rules),
+        player,
+        max_distance as u32,
+    )
+    .into_iter()
+    .filter(|it| can_spawn_at(&matrix, &level, &doors, &it.0))
+    .collect();
+    let placer = &mut *placer;
+
+    placer.distances.clear();
+    placer.distances.extend(distances.iter().copied());
+    placer.reachable.clear();
+    placer.reachable.extend(distances.iter().map(|it| it.0));
+    placer.from = Some(player);
+    placer.generations = generations;
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    game::{
+        brush::line,
+        chunk::{all_chunks, chunk_of},
+        coordinates::Coordinates,
+        level::{Decoration, Level, CRACK_STAGES, WALL_HEALTH},
+        matrix::Matrix,
+        node::Node,
+    },
+    GameMode, LivePosition, NodeSize, Player, PlayerPosition,
+};
+
+use super::{floor::FloorChanged, grid::OpenNodes};
+
+// pixels per side of the generated crack images
+const CRACK_SIZE: usize = 16;
+const CRACK_COLOR: [u8; 4] = [20, 14, 10, 230];
+// (row, col) pixel segments, every stage draws its own and those of the stages before
+const CRACKS: [&[(Coordinates, Coordinates)]; CRACK_STAGES as usize] = [
+    &[((7, 2), (8, 7)), ((8, 7), (5, 9))],
+    &[((5, 9), (3, 14)), ((8, 7), (13, 8))],
+    &[
+        ((13, 8), (15, 12)),
+        ((7, 2), (3, 0)),
+        ((5, 9), (10, 11)),
+        ((10, 11), (11, 15)),
+    ],
+];
+
+// seconds between dropping a bomb and its explosion
+const BOMB_FUSE: f32 = 2.;
+const BOMB_RADIUS: usize = 2;
+// enough for every wall within the radius to break, the falloff included
+const BOMB_DAMAGE: u16 = WALL_HEALTH as u16 * 2;
+// share of the cell covered by a bomb
+const BOMB_SIZE: f32 = 0.5;
+const BOMB_COLOR: Color = Color::rgb(0.12, 0.12, 0.15);
+
+// a single wall takes the damage, nothing happens when the cell is not a wall
+pub struct WallHit {
+    pub coordinates: Coordinates,
+    pub damage: u16,
+}
+
+// every wall within the radius in cells takes damage, half of it at the very edge
+pub struct Explosion {
+    pub center: Coordinates,
+    pub radius: usize,
+    pub damage: u16,
+}
+
+#[derive(Resource)]
+struct CrackTextures(Vec<Handle<Image>>);
+
+#[derive(Resource, Default)]
+struct Cracks(HashMap<Coordinates, Entity>);
+
+#[derive(Component)]
+struct Crack(Coordinates);
+
+#[derive(Component)]
+struct Bomb {
+    coordinates: Coordinates,
+    fuse: Timer,
+}
+
+pub struct WallPlugin;
+
+impl Plugin for WallPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cracks>()
+            .add_event::<WallHit>()
+            .add_event::<Explosion>()
+            .add_startup_system(setup_system)
+            .add_system_set(SystemSet::on_update(GameMode::Playing).with_system(drop_bomb_system))
+            .add_system(fuse_system.before(damage_walls_system))
+            .add_system(damage_walls_system)
+            .add_system(sync_cracks_system.after(damage_walls_system))
+            .add_system(render_cracks_system.after(sync_cracks_system))
+            .add_system(render_bombs_system)
+            .add_system(change_floor_system);
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn setup_system(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let handles = (1..=CRACKS.len())
+        .map(|stage| {
+            let mut data = vec![0; CRACK_SIZE * CRACK_SIZE * 4];
+
+            for (from, to) in CRACKS[..stage].iter().flat_map(|it| it.iter()) {
+                for pixel in line(from, to) {
+                    let offset = (pixel.0 * CRACK_SIZE + pixel.1) * 4;
+
+                    data[offset..offset + 4].copy_from_slice(&CRACK_COLOR);
+                }
+            }
+
+            images.add(Image::new(
+                Extent3d {
+                    width: CRACK_SIZE as u32,
+                    height: CRACK_SIZE as u32,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                data,
+                TextureFormat::Rgba8UnormSrgb,
+            ))
+        })
+        .collect();
+
+    commands.insert_resource(CrackTextures(handles));
+}
+
+// the player drops a bomb on their cell with B, one at a time
+fn drop_bomb_system(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    node_size: Res<NodeSize>,
+    p_query: Query<&PlayerPosition, With<Player>>,
+    b_query: Query<(), With<Bomb>>,
+) {
+    if !keys.just_pressed(KeyCode::B) || !b_query.is_empty() {
+        return;
+    }
+
+    for player_position in &p_query {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: BOMB_COLOR,
+                    custom_size: Some(Vec2::new(
+                        node_size.0 .0 * BOMB_SIZE,
+                        node_size.0 .1 * BOMB_SIZE,
+                    )),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0., 0., 99.7),
+                ..default()
+            },
+            Bomb {
+                coordinates: player_position.current_position.0,
+                fuse: Timer::from_seconds(BOMB_FUSE, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+fn fuse_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut explosion_events: EventWriter<Explosion>,
+    mut query: Query<(Entity, &mut Bomb)>,
+) {
+    for (entity, mut bomb) in &mut query {
+        if bomb.fuse.tick(time.delta()).just_finished() {
+            explosion_events.send(Explosion {
+                center: bomb.coordinates,
+                radius: BOMB_RADIUS,
+                damage: BOMB_DAMAGE,
+            });
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn change_floor_system(
+    mut commands: Commands,
+    mut floor_changed_events: EventReader<FloorChanged>,
+    query: Query<Entity, With<Bomb>>,
+) {
+    if floor_changed_events.iter().last().is_none() {
+        return;
+    }
+
+    for entity in &query {
+        commands.entity(entity).despawn();
+    }
+}
+
+// broken walls turn into rubble covered floor, pathing picks that up from the change log
+fn damage_walls_system(
+    mut wall_hit_events: EventReader<WallHit>,
+    mut explosion_events: EventReader<Explosion>,
+    mut matrix: ResMut<Matrix<Node>>,
+    mut level: ResMut<Level>,
+    mut open_nodes: ResMut<OpenNodes>,
+) {
+    let hits = wall_hit_events
+        .iter()
+        .map(|it| (it.coordinates, it.damage))
+        .collect::<Vec<_>>();
+    let blasts = explosion_events.iter().flat_map(|explosion| {
+        let (center, radius) = (explosion.center, explosion.radius);
+        let rows = center.0.saturating_sub(radius)..=center.0 + radius;
+        let cols = center.1.saturating_sub(radius)..=center.1 + radius;
+
+        rows.flat_map(move |row| cols.clone().map(move |col| (row, col)))
+            .filter_map(move |it| {
+                let distance = ((it.0.abs_diff(center.0).pow(2) + it.1.abs_diff(center.1).pow(2))
+                    as f32)
+                    .sqrt();
+
+                (distance <= radius as f32).then(|| {
+                    let falloff = 1. - distance / (radius.max(1) * 2) as f32;
+
+                    (it, (explosion.damage as f32 * falloff).round() as u16)
+                })
+            })
+    });
+
+    for (coordinates, damage) in hits.into_iter().chain(blasts) {
+        // cells of unloaded chunks read as walls but are not there to break
+        if damage == 0
+            || !matrix.contains(&coordinates)
+            || !matrix.is_chunk_loaded(&chunk_of(&coordinates))
+            || !matrix[coordinates].is_wall()
+        {
+            continue;
+        }
+
+        if level.damage_wall(coordinates, damage) {
+            matrix.set(coordinates, Node::open());
+            level.decoration.set(coordinates, Decoration::Rubble);
+            open_nodes.0.push(coordinates);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sync_cracks_system(
+    mut commands: Commands,
+    matrix: Res<Matrix<Node>>,
+    level: Res<Level>,
+    node_size: Res<NodeSize>,
+    crack_textures: Res<CrackTextures>,
+    mut cracks: ResMut<Cracks>,
+    mut query: Query<&mut Handle<Image>, With<Crack>>,
+    mut generation: Local<Option<(u64, u64)>>,
+) {
+    // walls crack without the node changing, so the damage has a log of its own
+    let generations = (
+        matrix.changes.generation(),
+        level.wall_damage.changes.generation(),
+    );
+    let last_generation = match *generation {
+        Some(value) => value,
+        None => {
+            *generation = Some(generations);
+
+            return;
+        }
+    };
+
+    if generations == last_generation {
+        return;
+    }
+
+    *generation = Some(generations);
+
+    let cells: Vec<Coordinates> = match (
+        matrix.changes.since(last_generation.0),
+        level.wall_damage.changes.since(last_generation.1),
+    ) {
+        (Some(nodes), Some(damage)) => nodes.into_iter().chain(damage).collect(),
+        // only loaded chunks can hold cracks, the ones shown so far are checked to go
+        _ => all_chunks(matrix.chunk_count())
+            .filter(|it| matrix.is_chunk_loaded(it))
+            .flat_map(|it| matrix.chunk_cells(&it))
+            .chain(cracks.0.keys().copied())
+            .collect(),
+    };
+
+    for coordinates in cells {
+        let stage = match matrix[coordinates].is_wall() {
+            true => level.crack_stage(coordinates),
+            false => 0,
+        };
+        let texture = match stage {
+            0 => None,
+            stage => crack_textures.0.get(stage as usize - 1).cloned(),
+        };
+
+        match (texture, cracks.0.get(&coordinates).copied()) {
+            (Some(texture), Some(entity)) => {
+                if let Ok(mut handle) = query.get_mut(entity) {
+                    *handle = texture;
+                }
+            }
+            (Some(texture), None) => {
+                let entity = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                custom_size: Some(Vec2::new(node_size.0 .0, node_size.0 .1)),
+                                ..default()
+                            },
+                            texture,
+                            transform: Transform::from_xyz(0., 0., 99.2),
+                            ..default()
+                        },
+                        Crack(coordinates),
+                    ))
+                    .id();
+
+                cracks.0.insert(coordinates, entity);
+            }
+            (None, Some(entity)) => {
+                commands.entity(entity).despawn();
+                cracks.0.remove(&coordinates);
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn render_cracks_system(
+    node_size: Res<NodeSize>,
+    p_query: Query<&LivePosition>,
+    mut query: Query<(&Crack, &mut Transform)>,
+) {
+    for live_position in &p_query {
+        for (crack, mut transform) in &mut query {
+            let center =
+                node_size.cell_center(live_position, (crack.0 .0 as f32, crack.0 .1 as f32));
+
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
+        }
+    }
+}
+
+fn render_bombs_system(
+    node_size: Res<NodeSize>,
+    p_query: Query<&LivePosition>,
+    mut query: Query<(&Bomb, &mut Transform)>,
+) {
+    for live_position in &p_query {
+        for (bomb, mut transform) in &mut query {
+            let center = node_size.cell_center(
+                live_position,
+                (bomb.coordinates.0 as f32, bomb.coordinates.1 as f32),
+            );
+
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    game::{
+        archetype::EnemyArchetype,
+        dungeon::{Dungeon, EnemySettings},
+        rng::GameRng,
+        wave::{Wave, WaveTable},
+    },
+    EnemyArchetypes, EnemyType, GameMode, NodeSize,
+};
+
+use super::{enemy::spawn_archetype, floor::FloorChanged, spawn::SpawnPlacer};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WaveStage {
+    Spawning { spawned: usize },
+    Breather,
+}
+
+// the wave counted from the start of the floor and how far it got
+#[derive(Resource)]
+struct WaveDirector {
+    index: usize,
+    stage: WaveStage,
+    elapsed: f32,
+}
+
+impl Default for WaveDirector {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            stage: WaveStage::Spawning { spawned: 0 },
+            elapsed: 0.,
+        }
+    }
+}
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaveDirector>()
+            .add_system(change_floor_system)
+            .add_system_set(SystemSet::on_update(GameMode::Playing).with_system(director_system));
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+// every floor starts over with the first wave
+fn change_floor_system(
+    mut director: ResMut<WaveDirector>,
+    mut floor_changed_events: EventReader<FloorChanged>,
+) {
+    if floor_changed_events.iter().last().is_some() {
+        *director = WaveDirector::default();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+// spawns are spread evenly over the time of the wave and held back while too many are alive
+fn director_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    dungeon: Res<Dungeon>,
+    wave_table: Res<WaveTable>,
+    node_size: Res<NodeSize>,
+    placer: Res<SpawnPlacer>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    enemy_settings: Res<EnemySettings>,
+    mut director: ResMut<WaveDirector>,
+    mut game_rng: ResMut<GameRng>,
+    query: Query<(), With<EnemyType>>,
+) {
+    if enemy_archetypes.loaded.is_empty() {
+        return;
+    }
+
+    let (wave, count) = match wave_table.wave(director.index, dungeon.floor) {
+        Some(wave) => wave,
+        None => return,
+    };
+
+    director.elapsed += time.delta_seconds();
+
+    let spawned = match director.stage {
+        WaveStage::Breather => {
+            if director.elapsed >= wave.breather {
+                *director = WaveDirector {
+                    index: director.index + 1,
+                    ..default()
+                };
+            }
+
+            return;
+        }
+        WaveStage::Spawning { spawned } => spawned,
+    };
+
+    let due = match wave.time > 0. {
+        true => ((director.elapsed / wave.time).min(1.) * count as f32).floor() as usize,
+        false => count,
+    };
+    let room = wave_table.max_alive.saturating_sub(query.iter().count());
+    let mut spawned_now = 0;
+
+    for _ in 0..due.saturating_sub(spawned).min(room) {
+        let rng = &mut game_rng.spawns;
+        let start = match placer.pick(rng, wave.ring, |_| true) {
+            Some(start) => start,
+            None => break,
+        };
+        let picked = match pick(wave, &enemy_archetypes, rng) {
+            Some(picked) => picked,
+            None => break,
+        };
+
+        spawn_archetype(
+            &mut commands,
+            &node_size,
+            &enemy_settings,
+            (&picked.0, &picked.1),
+            start,
+            start,
+            rng,
+        );
+        spawned_now += 1;
+    }
+
+    let spawned = spawned + spawned_now;
+
+    // the breather only starts once the whole wave is out, even when it was held back
+    director.stage = match spawned >= count && director.elapsed >= wave.time {
+        true => {
+            director.elapsed = 0.;
+
+            WaveStage::Breather
+        }
+        false => WaveStage::Spawning { spawned },
+    };
+}
+
+// by the weights of the mix, or of the archetypes themselves when the mix is empty
+fn pick<'a>(
+    wave: &Wave,
+    enemy_archetypes: &'a EnemyArchetypes,
+    rng: &mut StdRng,
+) -> Option<&'a (Handle<EnemyArchetype>, EnemyArchetype)> {
+    let mix: Vec<_> = wave
+        .mix
+        .iter()
+        .filter_map(|(name, weight)| enemy_archetypes.named(name).map(|it| (it, *weight)))
+        .collect();
+
+    match mix.is_empty() {
+        true => enemy_archetypes.pick(rng),
+        false => mix.choose_weighted(rng, |it| it.1).ok().map(|it| it.0),
+    }
+}